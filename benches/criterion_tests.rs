@@ -5,29 +5,150 @@ use criterion::Criterion;
 
 fn gen_uset(c: &mut Criterion) {
     c.bench_function("USet generate map 1000", |b| {
-        b.iter({ || gen_cities_uset(1000, 75) })
+        b.iter(|| gen_cities_uset(1000, 75))
     });
 }
 
 fn gen_hashset(c: &mut Criterion) {
     c.bench_function("HashSet generate map 1000", |b| {
-        b.iter({ || gen_cities_hashset(1000, 75) })
+        b.iter(|| gen_cities_hashset(1000, 75))
     });
 }
 
 fn solve(c: &mut Criterion) {
     let map = gen_cities_uset(1000, 75);
     c.bench_function("Solve map 1000", move |b| {
-        b.iter({ || find_city_distances(&map) })
+        b.iter(|| find_city_distances(&map))
     });
 }
 
-criterion_group!(benches, gen_uset, gen_hashset, solve);
+fn extend_naive(c: &mut Criterion) {
+    c.bench_function("USet extend by push 10k", |b| {
+        b.iter(|| {
+            let mut set = USet::new();
+            for id in (0..10_000).rev() {
+                set.push(id);
+            }
+            set
+        })
+    });
+}
+
+fn extend(c: &mut Criterion) {
+    c.bench_function("USet extend 10k", |b| {
+        b.iter(|| {
+            let mut set = USet::new();
+            set.extend((0..10_000).rev());
+            set
+        })
+    });
+}
+
+fn extend_map_naive(c: &mut Criterion) {
+    c.bench_function("UMap extend by put 10k", |b| {
+        b.iter(|| {
+            let mut map = UMap::new();
+            for id in (0..10_000).rev() {
+                map.put(id, id);
+            }
+            map
+        })
+    });
+}
+
+fn extend_map(c: &mut Criterion) {
+    c.bench_function("UMap extend 10k", |b| {
+        b.iter(|| {
+            let mut map = UMap::new();
+            map.extend((0..10_000).rev().map(|id| (id, id)));
+            map
+        })
+    });
+}
+
+fn collect_via_vec(c: &mut Criterion) {
+    c.bench_function("USet collect via Vec 10k", |b| {
+        b.iter(|| {
+            let vec: Vec<usize> = (0..10_000).rev().collect();
+            USet::from_slice(&vec)
+        })
+    });
+}
+
+fn collect(c: &mut Criterion) {
+    c.bench_function("USet collect 10k", |b| {
+        b.iter(|| (0..10_000).rev().collect::<USet>())
+    });
+}
+
+fn any_in_subset_full_scan(c: &mut Criterion) {
+    let map: UMap<usize> = (0..100_000).map(|id| (id, id)).collect();
+    let subset = USet::from_slice(&[10, 50_000, 99_000]);
+    c.bench_function("UMap any in subset by full scan 100k", move |b| {
+        b.iter(|| {
+            map.iter()
+                .any(|(id, value)| subset.contains(id) && *value == 1)
+        })
+    });
+}
+
+fn any_in_subset(c: &mut Criterion) {
+    let map: UMap<usize> = (0..100_000).map(|id| (id, id)).collect();
+    let subset = USet::from_slice(&[10, 50_000, 99_000]);
+    c.bench_function("UMap any in subset 100k", move |b| {
+        b.iter(|| map.any_in_subset(&subset, |value| *value == 1))
+    });
+}
+
+fn is_subset_of(c: &mut Criterion) {
+    let big = USet::from_range_where(0..1_000_000, |id| id % 3 != 0);
+    let small = USet::from_range_where(0..1_000_000, |id| id % 3 == 1);
+    c.bench_function("USet is_subset_of 1M", move |b| {
+        b.iter(|| small.is_subset_of(&big))
+    });
+}
+
+fn new_via_static(c: &mut Criterion) {
+    c.bench_function("USet new via EMPTY_SET 1k", |b| {
+        b.iter(|| {
+            (0..1000).for_each(|_| {
+                criterion::black_box(EMPTY_SET.clone());
+            })
+        })
+    });
+}
+
+fn new(c: &mut Criterion) {
+    c.bench_function("USet new 1k", |b| {
+        b.iter(|| {
+            (0..1000).for_each(|_| {
+                criterion::black_box(USet::new());
+            })
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    gen_uset,
+    gen_hashset,
+    solve,
+    extend_naive,
+    extend,
+    extend_map_naive,
+    extend_map,
+    collect_via_vec,
+    collect,
+    any_in_subset_full_scan,
+    any_in_subset,
+    is_subset_of,
+    new_via_static,
+    new
+);
 criterion_main!(benches);
 
 // ---
 
-use std;
 use std::cmp::min;
 
 extern crate rand;
@@ -35,7 +156,8 @@ extern crate rand;
 use rand::*;
 
 use std::collections::HashSet;
-use uset::core::uset::USet;
+use uset::core::umap::UMap;
+use uset::core::uset::{USet, EMPTY_SET};
 
 /// Calculates a vector where indexes are the distances from the capital and the values are
 /// the number of cities with the given distance.
@@ -56,7 +178,7 @@ use uset::core::uset::USet;
 /// ```
 pub fn find_city_distances(city_vec: &[usize]) -> Vec<usize> {
     let (capital, ..) = city_vec.iter().enumerate().find(|&(i, &c)| i == c).unwrap();
-    let mut distance_map = vec![std::usize::MAX; city_vec.len()];
+    let mut distance_map = vec![usize::MAX; city_vec.len()];
     distance_map[capital] = 0;
 
     for i in 0..city_vec.len() {
@@ -91,7 +213,7 @@ fn find_path(
     distance_map: &[usize],
     path: &mut Vec<usize>,
 ) -> usize {
-    if distance_map[i] == std::usize::MAX {
+    if distance_map[i] == usize::MAX {
         path.push(i);
         find_path(city_array[i], city_array, distance_map, path) // should be tailrec
     } else {
@@ -141,7 +263,7 @@ fn gen_unshuffled(
     city_vec
 }
 
-fn city_swap(city_vec: &mut Vec<usize>, from: usize, to: usize) {
+fn city_swap(city_vec: &mut [usize], from: usize, to: usize) {
     city_vec.swap(from, to);
     for v in city_vec.iter_mut().skip(1) {
         if *v == from {
@@ -152,7 +274,7 @@ fn city_swap(city_vec: &mut Vec<usize>, from: usize, to: usize) {
     }
 }
 
-fn shuffle_cities(mut city_vec: &mut Vec<usize>) {
+fn shuffle_cities(city_vec: &mut [usize]) {
     debug_assert!(city_vec.len() > 1);
     let mut r = rand::thread_rng();
 
@@ -160,12 +282,12 @@ fn shuffle_cities(mut city_vec: &mut Vec<usize>) {
         let from = r.gen_range(1, city_vec.len());
         let to = r.gen_range(1, city_vec.len());
         if from != to {
-            city_swap(&mut city_vec, from, to);
+            city_swap(city_vec, from, to);
         }
     }
 
     let capital_switch = r.gen_range(1, city_vec.len());
-    city_swap(&mut city_vec, 0, capital_switch);
+    city_swap(city_vec, 0, capital_switch);
 }
 
 /// Generates a city map.
@@ -233,7 +355,7 @@ pub fn gen_cities_uset(size: usize, max_roads_per_distance: usize) -> Vec<usize>
         let max_cities = min(new_cities, free_cities.len());
 
         for _i in 0..max_cities {
-            let new_city = pop_random(&mut free_cities).unwrap();
+            let new_city = free_cities.pop_random(&mut r).unwrap();
             city_vec.push((new_city, city));
         }
     }
@@ -245,15 +367,6 @@ pub fn gen_cities_uset(size: usize, max_roads_per_distance: usize) -> Vec<usize>
     city_array
 }
 
-fn pop_random(set: &mut USet) -> Option<usize> {
-    if !set.is_empty() {
-        let index = rand::thread_rng().gen_range(0, set.len());
-        set.pop(index)
-    } else {
-        None
-    }
-}
-
 /// Generates a city map.
 ///
 /// Same as `gen_cities_uset` but uses `std::collections::HashSet` instead of `USet`.
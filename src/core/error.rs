@@ -0,0 +1,20 @@
+use std::error::Error;
+use std::fmt;
+
+/// Errors returned by the fallible constructors of `USet` and `UMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsetError {
+    /// The span between the smallest and the largest identifier is bigger than the allowed
+    /// limit, so the internal vector would be too big to allocate safely.
+    TooLarge,
+}
+
+impl fmt::Display for UsetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UsetError::TooLarge => write!(f, "the span of identifiers is too large"),
+        }
+    }
+}
+
+impl Error for UsetError {}
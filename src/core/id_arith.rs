@@ -0,0 +1,107 @@
+use std::fmt;
+
+/// The policy for arithmetic on identifiers which could go below 0 or above `usize::MAX`.
+/// Used by operations which move identifiers around, e.g. [`USet::shift_with`].
+///
+/// Note that `USet` and `UMap` allocate memory for the whole span between the smallest and
+/// the largest identifier, so a collection with identifiers both near 0 and near `usize::MAX`
+/// is practically impossible to allocate. Operations which would wrap only some of the
+/// identifiers over to the other end of the range fail with [`IdOverflow`] instead.
+///
+/// The default is `Checked`.
+///
+/// [`USet::shift_with`]: ../uset/struct.USet.html#method.shift_with
+/// [`IdOverflow`]: struct.IdOverflow.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdArith {
+    /// The operation fails with [`IdOverflow`] if any identifier would go out of range.
+    ///
+    /// [`IdOverflow`]: struct.IdOverflow.html
+    Checked,
+    /// Identifiers which would go out of range are clamped to 0 or `usize::MAX`.
+    Saturating,
+    /// Identifiers which would go out of range wrap around, modulo `usize::MAX + 1`.
+    Wrapping,
+}
+
+impl Default for IdArith {
+    fn default() -> Self {
+        IdArith::Checked
+    }
+}
+
+/// The error returned when an identifier goes out of range under [`IdArith::Checked`].
+/// Holds the original identifier for which the operation failed.
+///
+/// [`IdArith::Checked`]: enum.IdArith.html#variant.Checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdOverflow {
+    pub id: usize,
+}
+
+impl fmt::Display for IdOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "id {} goes out of range", self.id)
+    }
+}
+
+impl IdArith {
+    /// Adds `delta` to `id` according to the policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::id_arith::*;
+    ///
+    /// assert_eq!(Ok(7), IdArith::Checked.add(5, 2));
+    /// assert_eq!(Err(IdOverflow { id: usize::MAX }), IdArith::Checked.add(usize::MAX, 1));
+    /// assert_eq!(Ok(usize::MAX), IdArith::Saturating.add(usize::MAX, 1));
+    /// assert_eq!(Ok(0), IdArith::Wrapping.add(usize::MAX, 1));
+    /// ```
+    pub fn add(self, id: usize, delta: usize) -> Result<usize, IdOverflow> {
+        match self {
+            IdArith::Checked => id.checked_add(delta).ok_or(IdOverflow { id }),
+            IdArith::Saturating => Ok(id.saturating_add(delta)),
+            IdArith::Wrapping => Ok(id.wrapping_add(delta)),
+        }
+    }
+
+    /// Subtracts `delta` from `id` according to the policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::id_arith::*;
+    ///
+    /// assert_eq!(Ok(3), IdArith::Checked.sub(5, 2));
+    /// assert_eq!(Err(IdOverflow { id: 0 }), IdArith::Checked.sub(0, 1));
+    /// assert_eq!(Ok(0), IdArith::Saturating.sub(0, 1));
+    /// assert_eq!(Ok(usize::MAX), IdArith::Wrapping.sub(0, 1));
+    /// ```
+    pub fn sub(self, id: usize, delta: usize) -> Result<usize, IdOverflow> {
+        match self {
+            IdArith::Checked => id.checked_sub(delta).ok_or(IdOverflow { id }),
+            IdArith::Saturating => Ok(id.saturating_sub(delta)),
+            IdArith::Wrapping => Ok(id.wrapping_sub(delta)),
+        }
+    }
+
+    /// Moves `id` by a signed `delta` according to the policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::id_arith::*;
+    ///
+    /// assert_eq!(Ok(3), IdArith::Checked.shift(5, -2));
+    /// assert_eq!(Ok(7), IdArith::Checked.shift(5, 2));
+    /// assert_eq!(Ok(0), IdArith::Saturating.shift(1, -2));
+    /// ```
+    pub fn shift(self, id: usize, delta: isize) -> Result<usize, IdOverflow> {
+        if delta < 0 {
+            self.sub(id, delta.unsigned_abs())
+        } else {
+            self.add(id, delta as usize)
+        }
+    }
+}
@@ -1,3 +1,6 @@
+pub mod error;
+pub mod id_arith;
+mod realloc_hook;
 pub mod umap;
 pub mod uset;
 
@@ -0,0 +1,56 @@
+use std::fmt;
+
+/// A callback invoked by `USet` and `UMap` whenever their internal vector is replaced
+/// or resized, with the old and the new capacity as arguments.
+///
+/// The callback can be set only with the `realloc-hook` feature. Without it, `ReallocHook`
+/// is an empty struct and calling it compiles to nothing.
+///
+/// The hook belongs to one instance of a collection: a clone of the collection starts
+/// without a hook, but operations replacing the collection's contents in place keep it.
+#[derive(Default)]
+pub struct ReallocHook {
+    #[cfg(feature = "realloc-hook")]
+    callback: Option<Box<dyn FnMut(usize, usize) + Send + Sync>>,
+}
+
+impl ReallocHook {
+    #[cfg(feature = "realloc-hook")]
+    pub(crate) fn set(&mut self, f: impl FnMut(usize, usize) + Send + Sync + 'static) {
+        self.callback = Some(Box::new(f));
+    }
+
+    #[cfg(feature = "realloc-hook")]
+    #[inline]
+    pub(crate) fn call(&mut self, old_capacity: usize, new_capacity: usize) {
+        if let Some(f) = self.callback.as_mut() {
+            f(old_capacity, new_capacity);
+        }
+    }
+
+    #[cfg(not(feature = "realloc-hook"))]
+    #[inline(always)]
+    pub(crate) fn call(&mut self, _old_capacity: usize, _new_capacity: usize) {}
+}
+
+impl Clone for ReallocHook {
+    fn clone(&self) -> Self {
+        ReallocHook::default()
+    }
+}
+
+impl fmt::Debug for ReallocHook {
+    #[cfg(feature = "realloc-hook")]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.callback.is_some() {
+            write!(f, "ReallocHook(set)")
+        } else {
+            write!(f, "ReallocHook(none)")
+        }
+    }
+
+    #[cfg(not(feature = "realloc-hook"))]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ReallocHook(none)")
+    }
+}
@@ -1,14 +1,28 @@
 #![macro_use]
 
-use super::uset::USet;
+use super::realloc_hook::ReallocHook;
+use super::uset::{span_summary, USet};
 use itertools::{Itertools, MinMaxResult};
+use std::borrow::Cow;
 use std::clone::Clone;
 use std::cmp;
+use std::collections::HashMap;
+use std::convert::TryInto;
 use std::fmt;
-use std::ops::Add;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Range, RangeInclusive};
 
 use std::iter::FromIterator;
 
+/// Creates a `UMap` with the given pairs of identifiers and elements.
+/// Equivalent to calling [`from_slice`].
+///
+/// [`from_slice`]: #method.from_slice
+#[allow(unused_macros)]
+macro_rules! umap {
+    ($($x:expr),*) => (UMap::from_slice(&[$($x),*]))
+}
+
 /// A map of unsigned integers (usizes) to values of the type T implementing `PartialEq` and `Clone`.
 /// The map is implemented as a vector of options of T, where `vec[n - offset] == Some(t)` means that
 /// the set contains the value `t` under the index `n`. Intended for handling small to medium number
@@ -28,16 +42,6 @@ use std::iter::FromIterator;
 /// fulfill certain conditions, carry them around, as they are much lightweight than the map,
 /// perform operations on them, and only at the end use them to retrieve the elements or make
 /// changes to the map.
-
-/// Creates a `UMap` with the given pairs of identifiers and elements.
-/// Equivalent to calling [`from_slice`].
-///
-/// [`from_slice`]: #method.from_slice
-#[allow(unused_macros)]
-macro_rules! umap {
-    ($($x:expr),*) => (UMap::from_slice(&vec![$($x),*]))
-}
-
 #[derive(Default, Clone)]
 pub struct UMap<T> {
     pub vec: Vec<Option<T>>,
@@ -45,6 +49,7 @@ pub struct UMap<T> {
     offset: usize,
     min: usize,
     max: usize,
+    realloc_hook: ReallocHook,
 }
 
 #[derive(Debug, Clone)]
@@ -83,15 +88,232 @@ where
             let index = len - self.rindex - 1;
             self.rindex += 1;
             if let Some(ref value) = self.handle.vec[index] {
-                return Some((index + self.handle.offset, &value));
+                return Some((index + self.handle.offset, value));
             }
         }
         None
     }
 }
 
+/// A read-only view of the elements of a `UMap` with identifiers belonging to a given set.
+/// Holds a reference to the map and the set of identifiers present in both, so no value
+/// is cloned. Constructed by the [`submap_ref`] method on `UMap`.
+///
+/// [`submap_ref`]: struct.UMap.html#method.submap_ref
+#[derive(Debug, Clone)]
+pub struct UMapView<'a, T: 'a> {
+    map: &'a UMap<T>,
+    keys: USet,
+}
+
+impl<'a, T> UMapView<'a, T>
+where
+    T: Clone + PartialEq,
+{
+    /// Returns a reference to the element under `id`, or `None` if `id` is not in the view.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    /// use self::uset::core::uset::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, "a"), (2, "b")]);
+    /// let set = USet::from_slice(&[1]);
+    /// let view = map.submap_ref(&set);
+    /// assert_eq!(Some(&"a"), view.get_ref(1));
+    /// assert_eq!(None, view.get_ref(2));
+    /// ```
+    pub fn get_ref(&self, id: usize) -> Option<&'a T> {
+        if self.keys.contains(id) {
+            self.map.get_ref(id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over the elements in the view, in ascending order of identifiers.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &'a T)> + '_ {
+        let map = self.map;
+        self.keys
+            .iter()
+            .filter_map(move |id| map.get_ref(id).map(|value| (id, value)))
+    }
+
+    /// Returns the set of identifiers in the view.
+    pub fn keys(&self) -> &USet {
+        &self.keys
+    }
+
+    /// Returns the number of elements in the view.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if the view contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns `true` if all elements in the view fulfill the `predicate`.
+    pub fn all(&self, predicate: impl Fn(&T) -> bool) -> bool {
+        self.iter().all(|(_id, value)| predicate(value))
+    }
+
+    /// Returns `true` if any element in the view fulfills the `predicate`.
+    pub fn any(&self, predicate: impl Fn(&T) -> bool) -> bool {
+        self.iter().any(|(_id, value)| predicate(value))
+    }
+}
+
+/// A view into a single slot in a `UMap`, which may either be occupied or vacant.
+/// Constructed by the [`entry`] method on `UMap`.
+///
+/// [`entry`]: struct.UMap.html#method.entry
+pub struct Entry<'a, T: 'a> {
+    map: &'a mut UMap<T>,
+    id: usize,
+}
+
+impl<'a, T> Entry<'a, T>
+where
+    T: Clone + PartialEq,
+{
+    /// Returns the identifier of the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map: UMap<&str> = UMap::new();
+    /// assert_eq!(3, map.entry(3).key());
+    /// ```
+    pub fn key(&self) -> usize {
+        self.id
+    }
+
+    /// Inserts `value` if the entry is vacant, and returns a mutable reference to the value
+    /// in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, 10)]);
+    /// *map.entry(1).or_insert(0) += 1;
+    /// *map.entry(2).or_insert(0) += 1;
+    /// assert_eq!(map, UMap::from_slice(&[(1, 11), (2, 1)]));
+    /// ```
+    pub fn or_insert(self, value: T) -> &'a mut T {
+        self.or_insert_with(|| value)
+    }
+
+    /// Inserts the result of `f` if the entry is vacant, and returns a mutable reference to
+    /// the value in the entry. `f` is not called if the entry is occupied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map: UMap<String> = UMap::new();
+    /// map.entry(1).or_insert_with(|| "a".to_string()).push('b');
+    /// assert_eq!(Some(&"ab".to_string()), map.get_ref(1));
+    /// ```
+    pub fn or_insert_with(self, f: impl FnOnce() -> T) -> &'a mut T {
+        if !self.map.contains(self.id) {
+            self.map.put(self.id, f());
+        }
+        self.map.get_ref_mut(self.id).unwrap()
+    }
+
+    /// Inserts the default value of `T` if the entry is vacant, and returns a mutable reference
+    /// to the value in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map: UMap<usize> = UMap::new();
+    /// for &id in &[1, 3, 1, 1] {
+    ///     *map.entry(id).or_default() += 1;
+    /// }
+    /// assert_eq!(map, UMap::from_slice(&[(1, 3), (3, 1)]));
+    /// ```
+    pub fn or_default(self) -> &'a mut T
+    where
+        T: Default,
+    {
+        self.or_insert_with(T::default)
+    }
+
+    /// Calls `f` on the value in the entry if the entry is occupied, and returns the entry
+    /// for further use, e.g. with [`or_insert`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, 10)]);
+    /// map.entry(1).and_modify(|v| *v += 1).or_insert(0);
+    /// map.entry(2).and_modify(|v| *v += 1).or_insert(0);
+    /// assert_eq!(map, UMap::from_slice(&[(1, 11), (2, 0)]));
+    /// ```
+    ///
+    /// [`or_insert`]: #method.or_insert
+    pub fn and_modify(self, f: impl FnOnce(&mut T)) -> Self {
+        if let Some(value) = self.map.get_ref_mut(self.id) {
+            f(value);
+        }
+        self
+    }
+}
+
+/// The error returned by [`try_insert`] when the identifier is already in the map.
+/// Contains the value which was not inserted, and a copy of the value already in the map.
+///
+/// [`try_insert`]: struct.UMap.html#method.try_insert
+#[derive(Debug, Clone, PartialEq)]
+pub struct OccupiedError<T> {
+    pub id: usize,
+    pub existing: T,
+    pub value: T,
+}
+
+impl<T> fmt::Display for OccupiedError<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "failed to insert {:?}, id {} already holds {:?}",
+            self.value, self.id, self.existing
+        )
+    }
+}
+
 pub const INITIAL_CAPACITY: usize = 8;
 
+/// Decides what [`insert_all`] does when an identifier is already in the map.
+///
+/// [`insert_all`]: struct.UMap.html#method.insert_all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertPolicy {
+    /// The value already in the map is kept, as in [`put`].
+    ///
+    /// [`put`]: struct.UMap.html#method.put
+    KeepExisting,
+    /// The value already in the map is replaced, as in [`replace`].
+    ///
+    /// [`replace`]: struct.UMap.html#method.replace
+    Overwrite,
+}
+
 impl<T> UMap<T>
 where
     T: Clone + PartialEq,
@@ -143,9 +365,131 @@ where
             offset: 0,
             min: 0,
             max: 0,
+            realloc_hook: ReallocHook::default(),
+        }
+    }
+
+    /// Sets a callback invoked with the old and the new capacity whenever the internal vector
+    /// is replaced or resized: by [`put`], [`put_all`], [`extend_from_map`], [`append`], and
+    /// by the methods managing the capacity, like [`reserve_range`], [`shrink_to`] or
+    /// [`compact`]. The callback is kept when [`extend_from_map`] or [`append`] replace the
+    /// map's contents. Replaces the previous callback, if any. Available only with the
+    /// `realloc-hook` feature.
+    ///
+    /// [`put`]: #method.put
+    /// [`put_all`]: #method.put_all
+    /// [`extend_from_map`]: #method.extend_from_map
+    /// [`append`]: #method.append
+    /// [`reserve_range`]: #method.reserve_range
+    /// [`shrink_to`]: #method.shrink_to
+    /// [`compact`]: #method.compact
+    #[cfg(feature = "realloc-hook")]
+    pub fn on_realloc(&mut self, f: impl FnMut(usize, usize) + Send + Sync + 'static) {
+        self.realloc_hook.set(f);
+    }
+
+    /// Constructs a new, empty `UMap` able to hold elements with all identifiers from `min`
+    /// to `max` (inclusive) without reallocating.
+    ///
+    /// Contrary to [`with_capacity`], the reserved window does not have to start at 0, which
+    /// saves memory when the identifiers are known to be large.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max < min`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::with_range_capacity(100, 109);
+    /// assert_eq!(map.capacity(), 10);
+    ///
+    /// // These are all done without reallocating...
+    /// for i in (100..110).rev() {
+    ///     map.put(i, i * 2);
+    /// }
+    /// assert_eq!(map.capacity(), 10);
+    ///
+    /// // ...but this may make the vector reallocate
+    /// map.put(99, 198);
+    /// ```
+    ///
+    /// [`with_capacity`]: #method.with_capacity
+    pub fn with_range_capacity(min: usize, max: usize) -> Self {
+        assert!(min <= max, "min must not be greater than max");
+        UMap {
+            vec: vec![None; max - min + 1],
+            len: 0,
+            offset: min,
+            min: 0,
+            max: 0,
+            realloc_hook: ReallocHook::default(),
         }
     }
 
+    /// Checks the invariants of the map's internal state. Does nothing in release builds.
+    pub(crate) fn check_invariants(&self) {
+        debug_assert_eq!(
+            self.len,
+            self.vec.iter().filter(|v| v.is_some()).count(),
+            "len must be equal to the number of elements"
+        );
+        if !self.is_empty() {
+            debug_assert!(
+                self.offset <= self.min,
+                "offset must not be greater than min"
+            );
+            debug_assert!(self.min <= self.max, "min must not be greater than max");
+            debug_assert!(
+                self.max - self.offset < self.capacity(),
+                "max must be within the capacity"
+            );
+            debug_assert!(
+                self.vec[self.min - self.offset].is_some(),
+                "min must belong to the map"
+            );
+            debug_assert!(
+                self.vec[self.max - self.offset].is_some(),
+                "max must belong to the map"
+            );
+            debug_assert_eq!(
+                self.len,
+                self.vec[self.min - self.offset..=self.max - self.offset]
+                    .iter()
+                    .filter(|v| v.is_some())
+                    .count(),
+                "all elements must be between min and max"
+            );
+        }
+    }
+
+    /// Returns the number of slots needed to hold identifiers from `min` to `max`, inclusive.
+    /// Just as in [`from_slice`], the result is never smaller than [`INITIAL_CAPACITY`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max < min`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// assert_eq!(10, UMap::<&str>::capacity_for(100, 109));
+    /// assert_eq!(INITIAL_CAPACITY, UMap::<&str>::capacity_for(100, 102));
+    /// assert_eq!(UMap::<&str>::capacity_for(100, 109), UMap::from_slice(&[(100, "a"), (104, "b"), (109, "c")]).capacity());
+    /// assert_eq!(UMap::<&str>::capacity_for(100, 102), UMap::from_slice(&[(100, "a"), (102, "b")]).capacity());
+    /// ```
+    ///
+    /// [`from_slice`]: #method.from_slice
+    /// [`INITIAL_CAPACITY`]: constant.INITIAL_CAPACITY.html
+    pub fn capacity_for(min: usize, max: usize) -> usize {
+        assert!(min <= max, "min must not be greater than max");
+        cmp::max(INITIAL_CAPACITY, max - min + 1)
+    }
+
     /// Returns the number of elements in the map, also referred to as its 'length'.
     ///
     /// # Examples
@@ -160,6 +504,25 @@ where
         self.len
     }
 
+    /// Returns `true` if the stored length of the map is equal to the number of elements actually
+    /// in the internal vector. It should always be the case, unless the public `vec` was
+    /// modified directly: this is a sanity check for tests, which scans the whole vector
+    /// but does not allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (2, "c")]);
+    /// assert!(map.verify_len());
+    /// map.vec[0] = None;
+    /// assert!(!map.verify_len());
+    /// ```
+    pub fn verify_len(&self) -> bool {
+        self.vec.iter().filter(|v| v.is_some()).count() == self.len
+    }
+
     /// Returns `true` if the map contains no elements.
     ///
     /// # Examples
@@ -177,6 +540,47 @@ where
         self.len == 0
     }
 
+    /// Returns the fraction of identifiers between `min` and `max` (inclusive) which have
+    /// elements in the map, or 0.0 if the map is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// assert_eq!(0.0, UMap::<&str>::new().density());
+    /// assert_eq!(0.4, UMap::from_slice(&[(1, "a"), (2, "b"), (9, "c"), (10, "d")]).density());
+    /// ```
+    pub fn density(&self) -> f64 {
+        if self.is_empty() {
+            0.0
+        } else {
+            self.len as f64 / (self.max - self.min + 1) as f64
+        }
+    }
+
+    /// Returns a short description of the map, with its length, the span of its identifiers,
+    /// the number of runs of consecutive identifiers, and density, but without listing
+    /// the elements. Intended for logs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, "a"), (2, "b"), (9, "c"), (10, "d")]);
+    /// assert_eq!("UMap{len=4, span=1..=10, runs=2, density=0.40}", map.summary());
+    /// ```
+    pub fn summary(&self) -> String {
+        format!(
+            "UMap{{len={}, span={}, runs={}, density={:.2}}}",
+            self.len,
+            span_summary(self.min(), self.max()),
+            self.keys().count_runs(),
+            self.density()
+        )
+    }
+
     /// Returns the number of elements the map can hold without reallocating.
     ///
     /// # Examples
@@ -191,6 +595,47 @@ where
         self.vec.len()
     }
 
+    /// Returns the identifier corresponding to the first slot of the internal vector.
+    /// This is an implementation detail, useful mostly for debugging and for interop with
+    /// bitmaps: slot `n` of the internal vector corresponds to `offset() + n`.
+    /// For a non-empty map, `offset() <= min()` always holds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(100, "a"), (102, "b")]);
+    /// assert_eq!(100, map.offset());
+    /// ```
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the number of slots in the map's internal vector which lie outside of the span
+    /// between the smallest and the largest identifier, i.e. `capacity() - (max - min + 1)`.
+    /// Returns 0 for an empty map. Can be used to decide whether to call [`shrink_to_fit`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (3, "b")]);
+    /// assert_eq!(5, map.wasted_capacity());
+    /// map.shrink_to_fit();
+    /// assert_eq!(0, map.wasted_capacity());
+    /// ```
+    ///
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    pub fn wasted_capacity(&self) -> usize {
+        if self.is_empty() {
+            0
+        } else {
+            self.capacity() - (self.max - self.min + 1)
+        }
+    }
+
     /// Shrinks the map to the minimal size able to hold its elements.
     ///
     /// # Examples
@@ -206,6 +651,7 @@ where
     /// assert_eq!(1, map.capacity());
     /// ```
     pub fn shrink_to_fit(&mut self) {
+        let old_capacity = self.capacity();
         if !self.is_empty() && (self.vec[0].is_none() || self.vec[self.vec.len() - 1].is_none()) {
             let mut vec = vec![None; self.max - self.min + 1];
             for id in self.min..=self.max {
@@ -213,38 +659,202 @@ where
             }
             self.vec = vec;
             self.offset = self.min;
+            self.realloc_hook.call(old_capacity, self.capacity());
         } else if self.is_empty() && self.capacity() > 0 {
             self.vec = Vec::with_capacity(0);
+            self.realloc_hook.call(old_capacity, 0);
         }
+        self.check_invariants();
     }
 
-    /// Shortens the map, keeping the first `len` elements and dropping the rest.
-    /// If `len` is greater than the map's current length, this has no effect.
-    ///
-    /// The [`drain`] method can emulate `truncate`, but causes the excess
-    /// elements to be returned instead of dropped.
-    ///
-    /// This method does not shrink the map's capacity.
-    /// If you want to shrink the set's capacity, call `shrink_to_fit` afterwards.
+    /// Rebases the map so that the smallest identifier is at the beginning of the internal vector,
+    /// and shrinks the vector to the span between the smallest and the largest identifier.
+    /// The result is the same as calling [`rebase`] and then [`shrink_to_fit`], but the elements
+    /// are moved in place and there is at most one reallocation.
     ///
     /// # Examples
     ///
-    /// Truncating a five element map to two elements:
-    ///
     /// ```
     /// use self::uset::core::umap::*;
     ///
-    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c"), (4, "d"), (5, "e")]);
-    /// map.truncate(2);
-    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (2, "b")]));
+    /// let mut map = UMap::with_capacity(20);
+    /// map.put(5, "a");
+    /// map.put(8, "b");
+    /// assert_eq!(20, map.capacity());
+    /// map.compact();
+    /// assert_eq!(4, map.capacity());
+    /// assert_eq!(map, UMap::from_slice(&[(5, "a"), (8, "b")]));
     /// ```
     ///
-    /// No truncation occurs when `len` is greater than the vector's current length:
+    /// [`rebase`]: #method.rebase
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    pub fn compact(&mut self) {
+        let old_capacity = self.capacity();
+        if self.is_empty() {
+            if self.capacity() > 0 {
+                self.vec = Vec::with_capacity(0);
+            }
+        } else {
+            self.vec.truncate(self.max - self.offset + 1);
+            self.vec.drain(..self.min - self.offset);
+            self.vec.shrink_to_fit();
+            self.offset = self.min;
+        }
+        if self.capacity() != old_capacity {
+            self.realloc_hook.call(old_capacity, self.capacity());
+        }
+        self.check_invariants();
+    }
+
+    /// Puts a clone of `default` under every absent identifier between the smallest and
+    /// the largest identifier in the map, so that the map becomes contiguous and its length
+    /// equals `max - min + 1`. Values already in the map are kept. Does nothing if the map
+    /// is empty. No reallocation is performed.
+    ///
+    /// # Examples
     ///
     /// ```
     /// use self::uset::core::umap::*;
     ///
-    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c")]);
+    /// let mut map = UMap::from_slice(&[(1, "a"), (3, "c"), (5, "e")]);
+    /// map.fill_gaps("x");
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (2, "x"), (3, "c"), (4, "x"), (5, "e")]));
+    /// assert_eq!(map.len(), 5);
+    /// ```
+    pub fn fill_gaps(&mut self, default: T) {
+        if self.is_empty() {
+            return;
+        }
+        let (from, to) = (self.min - self.offset, self.max - self.offset);
+        self.vec[from..=to]
+            .iter_mut()
+            .filter(|value| value.is_none())
+            .for_each(|value| *value = Some(default.clone()));
+        self.len = self.max - self.min + 1;
+        self.check_invariants();
+    }
+
+    /// Returns the region of the internal vector between the smallest and the largest identifier
+    /// if the map is contiguous, i.e. if every identifier in `[min, max]` holds a value.
+    /// Otherwise, or if the map is empty, returns `None`.
+    ///
+    /// The map stores its values as `Option<T>`, so the window is a `&[Option<T>]` and not
+    /// a `&[T]`, but when it is returned all its elements are guaranteed to be `Some`.
+    /// The element at index `i` is the value under the identifier `min + i`.
+    /// No copying is performed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (3, "b"), (4, "c")]);
+    /// assert_eq!(Some(&[Some("a"), Some("b"), Some("c")][..]), map.occupied_window());
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (4, "c")]);
+    /// assert_eq!(None, map.occupied_window());
+    /// ```
+    pub fn occupied_window(&self) -> Option<&[Option<T>]> {
+        if self.is_empty() || self.len != self.max - self.min + 1 {
+            None
+        } else {
+            Some(&self.vec[self.min - self.offset..=self.max - self.offset])
+        }
+    }
+
+    /// Shrinks the capacity of the map with a lower bound.
+    /// The capacity will remain at least as large as both `min_capacity` and the size needed
+    /// to hold the elements (`max - min + 1`). If the current capacity is already less or equal
+    /// to that, this method does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (50, "b")]);
+    /// map.remove(1);
+    /// map.shrink_to(10);
+    /// assert_eq!(10, map.capacity());
+    /// map.shrink_to(0);
+    /// assert_eq!(1, map.capacity());
+    /// assert_eq!(map, UMap::from_slice(&[(50, "b")]));
+    /// ```
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let span = if self.is_empty() {
+            0
+        } else {
+            self.max - self.min + 1
+        };
+        let capacity = cmp::max(min_capacity, span);
+        if capacity < self.capacity() {
+            let old_capacity = self.capacity();
+            let mut vec = vec![None; capacity];
+            if !self.is_empty() {
+                for id in self.min..=self.max {
+                    vec[id - self.min] = self.get(id);
+                }
+                self.offset = self.min;
+            }
+            self.vec = vec;
+            self.realloc_hook.call(old_capacity, capacity);
+        }
+        self.check_invariants();
+    }
+
+    /// Moves the elements in the map's internal vector so that the element with the smallest
+    /// identifier is at `vec[0]` (i.e. `offset == min`). Unused slots before the minimum are moved
+    /// to the end of the vector, so the capacity does not change and no reallocation is performed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(0, "a"), (9, "b")]);
+    /// assert_eq!(10, map.capacity());
+    /// map.remove(0);
+    /// map.rebase();
+    /// assert_eq!(10, map.capacity());
+    /// map.put(18, "c"); // no reallocation needed
+    /// assert_eq!(10, map.capacity());
+    /// assert_eq!(map, UMap::from_slice(&[(9, "b"), (18, "c")]));
+    /// ```
+    pub fn rebase(&mut self) {
+        if !self.is_empty() && self.offset < self.min {
+            self.vec.rotate_left(self.min - self.offset);
+            self.offset = self.min;
+        }
+        self.check_invariants();
+    }
+
+    /// Shortens the map, keeping the first `len` elements and dropping the rest.
+    /// If `len` is greater than the map's current length, this has no effect.
+    ///
+    /// The [`drain`] method can emulate `truncate`, but causes the excess
+    /// elements to be returned instead of dropped.
+    ///
+    /// This method does not shrink the map's capacity.
+    /// If you want to shrink the set's capacity, call `shrink_to_fit` afterwards.
+    ///
+    /// # Examples
+    ///
+    /// Truncating a five element map to two elements:
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c"), (4, "d"), (5, "e")]);
+    /// map.truncate(2);
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (2, "b")]));
+    /// ```
+    ///
+    /// No truncation occurs when `len` is greater than the vector's current length:
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c")]);
     /// map.truncate(8);
     /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c")]));
     /// ```
@@ -290,6 +900,7 @@ where
             self.max = 0;
             self.len = 0;
         }
+        self.check_invariants();
     }
 
     /// Works like [`truncate`], but returns the removed elements in the form of a new map.
@@ -366,6 +977,7 @@ where
                 });
             self.max = new_max + self.offset;
             self.len = len;
+            self.check_invariants();
             new_map.shrink_to_fit(); // TODO integrate with populating the vector
             new_map
         } else if !self.is_empty() && len == 0 {
@@ -379,12 +991,89 @@ where
             self.min = 0;
             self.max = 0;
             self.len = 0;
+            self.check_invariants();
             new_map
         } else {
             UMap::with_capacity(0)
         }
     }
 
+    /// Removes all elements with identifiers within the given range from the map and returns
+    /// them as a new map. The elements are moved, not cloned.
+    /// This method does not shrink the map's capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (4, "c"), (5, "d"), (7, "e")]);
+    /// let drained = map.drain_range(2..6);
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (7, "e")]));
+    /// assert_eq!(drained, UMap::from_slice(&[(2, "b"), (4, "c"), (5, "d")]));
+    /// ```
+    pub fn drain_range(&mut self, range: Range<usize>) -> Self {
+        let from = cmp::max(range.start, self.min);
+        let to = cmp::min(range.end, self.max.saturating_add(1));
+        let (first, last) = if self.is_empty() {
+            (None, None)
+        } else {
+            (
+                (from..to).find(|&id| self.contains(id)),
+                (from..to).rev().find(|&id| self.contains(id)),
+            )
+        };
+        if let (Some(first), Some(last)) = (first, last) {
+            let mut drained = UMap::with_range_capacity(first, last);
+            for id in first..=last {
+                if let Some(value) = self.vec[id - self.offset].take() {
+                    drained.put(id, value);
+                }
+            }
+            self.len -= drained.len();
+            if self.is_empty() {
+                self.offset = 0;
+                self.min = 0;
+                self.max = 0;
+            } else if first == self.min {
+                self.min = (last + 1..=self.max)
+                    .find(|&id| self.vec[id - self.offset].is_some())
+                    .unwrap();
+            } else if last == self.max {
+                self.max = (self.min..first)
+                    .rev()
+                    .find(|&id| self.vec[id - self.offset].is_some())
+                    .unwrap();
+            }
+            self.check_invariants();
+            drained
+        } else {
+            UMap::new()
+        }
+    }
+
+    /// Returns an iterator which removes the elements from the map in the ascending order
+    /// of their identifiers, one by one, as they are produced. Values are moved, not cloned.
+    /// When fully consumed, the map is left empty. If the iterator is dropped early, only
+    /// the elements produced so far are removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (3, "b"), (8, "c")]);
+    /// let drained: Vec<(usize, &str)> = map.drain_iter().take(2).collect();
+    /// assert_eq!(vec![(1, "a"), (3, "b")], drained);
+    /// assert_eq!(map, UMap::from_slice(&[(8, "c")]));
+    /// ```
+    pub fn drain_iter(&mut self) -> impl Iterator<Item = (usize, T)> + '_ {
+        std::iter::from_fn(move || {
+            let id = self.min()?;
+            self.remove(id).map(|value| (id, value))
+        })
+    }
+
     /// Clears the map, removing all elements.
     ///
     /// Note that this method has no effect on the allocated capacity of the map.
@@ -407,6 +1096,43 @@ where
         self.truncate(0)
     }
 
+    /// Empties the map and prepares it for identifiers from `min` to `max` (inclusive), as if it
+    /// was created with [`with_range_capacity`], but reusing the internal vector: it is only
+    /// enlarged if it is too small for the new window, and never shrunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max < min`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (10, "b")]);
+    /// map.reset_to(100, 104);
+    /// assert!(map.is_empty());
+    /// assert_eq!(10, map.capacity());
+    /// map.put(104, "c");
+    /// assert_eq!(10, map.capacity());
+    /// ```
+    ///
+    /// [`with_range_capacity`]: #method.with_range_capacity
+    pub fn reset_to(&mut self, min: usize, max: usize) {
+        assert!(min <= max, "min must not be greater than max");
+        self.vec.iter_mut().for_each(|value| *value = None);
+        let old_capacity = self.capacity();
+        if old_capacity < max - min + 1 {
+            self.vec.resize(max - min + 1, None);
+            self.realloc_hook.call(old_capacity, self.capacity());
+        }
+        self.len = 0;
+        self.offset = min;
+        self.min = 0;
+        self.max = 0;
+        self.check_invariants();
+    }
+
     /// Changes the map's capacity, so that it can hold new elements up to the `new_capacity + offset - 1`
     /// value without reallocation. Note that `new_capacity + offset - 1` is now the largest **identifier**
     /// the map can hold without the reallocation, not the total number of values that can be held.
@@ -430,8 +1156,100 @@ where
     /// ```
     pub fn enlarge_capacity_to(&mut self, new_capacity: usize) {
         if new_capacity > self.capacity() {
+            let old_capacity = self.capacity();
             self.vec.resize(new_capacity, None);
+            self.realloc_hook.call(old_capacity, new_capacity);
+        }
+        self.check_invariants();
+    }
+
+    /// Makes sure the id falls within the allocated window, without putting anything under it.
+    /// If the id is lower than the current offset, the window is extended downwards.
+    /// A subsequent [`put`] under the id will not trigger reallocation.
+    ///
+    /// [`put`]: #method.put
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(5, "a"), (8, "b")]);
+    /// map.grow_to_include(2);
+    /// assert_eq!(11, map.capacity());
+    /// assert!(!map.contains(2));
+    /// assert_eq!(2, map.len());
+    /// map.put(2, "c"); // no reallocation needed
+    /// assert_eq!(11, map.capacity());
+    /// ```
+    pub fn grow_to_include(&mut self, id: usize) {
+        let old_capacity = self.capacity();
+        if self.capacity() == 0 {
+            self.vec = vec![None; INITIAL_CAPACITY];
+            self.offset = id;
+            self.realloc_hook.call(old_capacity, self.capacity());
+        } else if self.is_empty() && (id < self.offset || id >= self.offset + self.capacity()) {
+            self.offset = id;
+        } else if id < self.offset {
+            let extra = self.offset - id;
+            self.vec
+                .splice(0..0, std::iter::repeat_with(|| None).take(extra));
+            self.offset = id;
+            self.realloc_hook.call(old_capacity, self.capacity());
+        } else if id >= self.offset + self.capacity() {
+            self.vec.resize(id + 1 - self.offset, None);
+            self.realloc_hook.call(old_capacity, self.capacity());
+        }
+        self.check_invariants();
+    }
+
+    /// Makes sure the whole range of identifiers `[min, max]` falls within the allocated window,
+    /// adjusting the offset downwards if needed. A batch of [`put`]s with identifiers from that
+    /// range will not trigger reallocation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max < min`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(10, "a"), (12, "b")]);
+    /// map.reserve_range(4, 20);
+    /// let capacity = map.capacity();
+    /// map.put(4, "c");
+    /// map.put(20, "d");
+    /// assert_eq!(capacity, map.capacity());
+    /// assert_eq!(4, map.len());
+    /// ```
+    ///
+    /// [`put`]: #method.put
+    pub fn reserve_range(&mut self, min: usize, max: usize) {
+        assert!(min <= max, "min must not be greater than max");
+        let old_capacity = self.capacity();
+        if self.is_empty() {
+            if self.capacity() < max - min + 1 {
+                self.vec = vec![None; max - min + 1];
+                self.realloc_hook.call(old_capacity, self.capacity());
+            }
+            self.offset = min;
+        } else {
+            let end = self.offset + self.capacity() - 1;
+            let new_offset = cmp::min(self.offset, min);
+            let new_end = cmp::max(end, max);
+            if new_offset < self.offset || new_end > end {
+                let mut vec: Vec<Option<T>> = Vec::with_capacity(new_end - new_offset + 1);
+                vec.extend(std::iter::repeat_with(|| None).take(self.offset - new_offset));
+                vec.append(&mut self.vec);
+                vec.resize_with(new_end - new_offset + 1, || None);
+                self.vec = vec;
+                self.offset = new_offset;
+                self.realloc_hook.call(old_capacity, self.capacity());
+            }
         }
+        self.check_invariants();
     }
 
     /// Adds the element at the end of the map and returns its new identifier.
@@ -481,12 +1299,19 @@ where
         match id {
             _ if self.capacity() == 0 => {
                 self.vec = vec![None; INITIAL_CAPACITY];
+                self.realloc_hook.call(0, INITIAL_CAPACITY);
                 self.vec[0] = Some(value);
                 self.min = id;
                 self.len += 1;
                 self.max = id;
                 self.offset = id;
             }
+            _ if self.is_empty() && id >= self.offset && id < self.offset + self.capacity() => {
+                self.vec[id - self.offset] = Some(value);
+                self.min = id;
+                self.len = 1;
+                self.max = id;
+            }
             _ if self.is_empty() => {
                 self.vec[0] = Some(value);
                 self.min = id;
@@ -500,13 +1325,17 @@ where
                 for i in self.min..=self.max {
                     vec[i - id] = self.get(i);
                 }
+                let old_capacity = self.capacity();
                 self.vec = vec;
+                self.realloc_hook.call(old_capacity, self.capacity());
                 self.len += 1;
                 self.min = id;
                 self.offset = id;
             }
             _ if id >= self.offset + self.capacity() => {
+                let old_capacity = self.capacity();
                 self.vec.resize(id + 1 - self.offset, None);
+                self.realloc_hook.call(old_capacity, self.capacity());
                 self.vec[id - self.offset] = Some(value);
                 self.len += 1;
                 self.max = id;
@@ -522,6 +1351,37 @@ where
             }
             _ => {}
         }
+        self.check_invariants();
+    }
+
+    /// Adds the element with the given id to the map and returns a mutable reference to it,
+    /// but only if the map does not already contain an element under that id.
+    /// Otherwise, the map is not changed and an error with both values is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a")]);
+    /// assert_eq!(Ok(&mut "b"), map.try_insert(2, "b"));
+    /// let err = map.try_insert(1, "c").unwrap_err();
+    /// assert_eq!(1, err.id);
+    /// assert_eq!("a", err.existing);
+    /// assert_eq!("c", err.value);
+    /// assert_eq!(Some("a"), map.get(1));
+    /// ```
+    pub fn try_insert(&mut self, id: usize, value: T) -> Result<&mut T, OccupiedError<T>> {
+        if let Some(existing) = self.get(id) {
+            Err(OccupiedError {
+                id,
+                existing,
+                value,
+            })
+        } else {
+            self.put(id, value);
+            Ok(self.get_ref_mut(id).unwrap())
+        }
     }
 
     /// Returns `true` if the map contains the given id.
@@ -537,7 +1397,22 @@ where
     /// assert_eq!(1, map.len());
     /// ```
     pub fn contains(&self, id: usize) -> bool {
-        id >= self.min && id <= self.max && self.vec[id - self.offset].is_some()
+        !self.is_empty() && id >= self.min && id <= self.max && self.vec[id - self.offset].is_some()
+    }
+
+    /// Returns `true` if the map contains an element equal to the given value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, String::from("a")), (3, String::from("b"))]);
+    /// assert!(map.contains_value(&String::from("b")));
+    /// assert!(!map.contains_value(&String::from("c")));
+    /// ```
+    pub fn contains_value(&self, value: &T) -> bool {
+        self.iter().any(|(_id, v)| v == value)
     }
 
     /// Returns `Some` with a copy of the element under the given id, or `None` otherwise.
@@ -553,13 +1428,31 @@ where
     /// assert_eq!(None, c);
     /// ```
     pub fn get(&self, id: usize) -> Option<T> {
-        if id >= self.min && id <= self.max {
+        if !self.is_empty() && id >= self.min && id <= self.max {
             unsafe { self.vec.get_unchecked(id - self.offset).clone() }
         } else {
             None
         }
     }
 
+    /// Returns a vector of copies of the elements under the given ids, in the same order as `ids`.
+    /// For ids which do not belong to the map, the vector contains `None`.
+    /// Contrary to [`retrieve`], it does not skip missing ids and it takes a slice instead of `USet`.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, String::from("a")), (2, String::from("b"))]);
+    /// let values = map.get_many(&[2, 3, 1]);
+    /// assert_eq!(vec![Some(String::from("b")), None, Some(String::from("a"))], values);
+    /// ```
+    ///
+    /// [`retrieve`]: #method.retrieve
+    pub fn get_many(&self, ids: &[usize]) -> Vec<Option<T>> {
+        ids.iter().map(|&id| self.get(id)).collect()
+    }
+
     /// Returns `Some` with a reference to the element under the given id, or `None` otherwise.
     ///
     /// # Examples
@@ -573,7 +1466,7 @@ where
     /// assert_eq!(None, c);
     /// ```
     pub fn get_ref(&self, id: usize) -> Option<&T> {
-        if id >= self.min && id <= self.max {
+        if !self.is_empty() && id >= self.min && id <= self.max {
             unsafe {
                 if let Some(ref v) = self.vec.get_unchecked(id - self.offset) {
                     Some(v)
@@ -586,6 +1479,65 @@ where
         }
     }
 
+    /// Returns `Some` with the given id and a reference to the element under it, or `None`
+    /// if the map does not contain the id.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, String::from("a")), (2, String::from("b"))]);
+    /// assert_eq!(Some((2, &String::from("b"))), map.get_key_value(2));
+    /// assert_eq!(None, map.get_key_value(3));
+    /// ```
+    pub fn get_key_value(&self, id: usize) -> Option<(usize, &T)> {
+        self.get_ref(id).map(|value| (id, value))
+    }
+
+    /// Returns the element with the smallest identifier greater than or equal to `id`,
+    /// together with that identifier, or `None` if there is no such element.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (5, "b"), (9, "c")]);
+    /// assert_eq!(Some((5, &"b")), map.ceiling_entry(3));
+    /// assert_eq!(Some((5, &"b")), map.ceiling_entry(5));
+    /// assert_eq!(Some((2, &"a")), map.ceiling_entry(0));
+    /// assert_eq!(None, map.ceiling_entry(10));
+    /// ```
+    pub fn ceiling_entry(&self, id: usize) -> Option<(usize, &T)> {
+        if self.is_empty() || id > self.max {
+            None
+        } else {
+            (cmp::max(id, self.min)..=self.max).find_map(|i| self.get_key_value(i))
+        }
+    }
+
+    /// Returns the element with the largest identifier less than or equal to `id`,
+    /// together with that identifier, or `None` if there is no such element.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (5, "b"), (9, "c")]);
+    /// assert_eq!(Some((5, &"b")), map.floor_entry(8));
+    /// assert_eq!(Some((5, &"b")), map.floor_entry(5));
+    /// assert_eq!(Some((9, &"c")), map.floor_entry(100));
+    /// assert_eq!(None, map.floor_entry(1));
+    /// ```
+    pub fn floor_entry(&self, id: usize) -> Option<(usize, &T)> {
+        if self.is_empty() || id < self.min {
+            None
+        } else {
+            (self.min..=cmp::min(id, self.max))
+                .rev()
+                .find_map(|i| self.get_key_value(i))
+        }
+    }
+
     /// Returns `Some` with a mutable reference to the element under the given id, or `None` otherwise.
     ///
     /// # Examples
@@ -602,7 +1554,7 @@ where
     /// assert_eq!(None, c);
     /// ```
     pub fn get_ref_mut(&mut self, id: usize) -> Option<&mut T> {
-        if id >= self.min && id <= self.max {
+        if !self.is_empty() && id >= self.min && id <= self.max {
             unsafe {
                 if let Some(ref mut v) = self.vec.get_unchecked_mut(id - self.offset) {
                     Some(v)
@@ -615,8 +1567,52 @@ where
         }
     }
 
-    /// Removes the element from the map and returns it.
-    /// Does nothing if the element with the given id is not in the map (returns `None`).
+    /// Returns mutable references to the elements under all of the given identifiers at once,
+    /// or `None` if any of the identifiers is not in the map or if any of them repeats.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, 10), (2, 20), (3, 30)]);
+    /// if let Some([a, b]) = map.get_many_mut([1, 3]) {
+    ///     std::mem::swap(a, b);
+    /// }
+    /// assert_eq!(map, UMap::from_slice(&[(1, 30), (2, 20), (3, 10)]));
+    /// assert!(map.get_many_mut([1, 1]).is_none());
+    /// assert!(map.get_many_mut([1, 4]).is_none());
+    /// ```
+    pub fn get_many_mut<const N: usize>(&mut self, ids: [usize; N]) -> Option<[&mut T; N]> {
+        if ids.iter().any(|&id| !self.contains(id)) {
+            return None;
+        }
+        let mut order: Vec<usize> = (0..N).collect();
+        order.sort_unstable_by_key(|&i| ids[i]);
+        if order.windows(2).any(|w| ids[w[0]] == ids[w[1]]) {
+            return None;
+        }
+
+        // Walk the ids in the ascending order, each time splitting off the slot of the next id
+        // from the remaining tail of the vector, so that all the references are disjoint.
+        let mut refs: Vec<Option<&mut T>> = (0..N).map(|_| None).collect();
+        let mut tail = &mut self.vec[..];
+        let mut tail_start = self.offset;
+        for &i in &order {
+            let (_, rest) = std::mem::take(&mut tail).split_at_mut(ids[i] - tail_start);
+            let (slot, rest) = rest.split_first_mut()?;
+            refs[i] = slot.as_mut();
+            tail = rest;
+            tail_start = ids[i] + 1;
+        }
+        refs.into_iter()
+            .collect::<Option<Vec<&mut T>>>()?
+            .try_into()
+            .ok()
+    }
+
+    /// Swaps the elements under the identifiers `a` and `b` without cloning them, and returns
+    /// `true`. Does nothing and returns `false` if either identifier is not in the map,
+    /// or if `a == b`.
     ///
     /// # Examples
     ///
@@ -624,16 +1620,54 @@ where
     /// use self::uset::core::umap::*;
     ///
     /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c")]);
-    /// let b = map.remove(2);
-    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (3, "c")]));
-    /// assert_eq!(b, Some("b"))
+    /// assert!(map.swap(1, 3));
+    /// assert_eq!(map, UMap::from_slice(&[(1, "c"), (2, "b"), (3, "a")]));
+    /// assert!(!map.swap(1, 4));
+    /// assert!(!map.swap(2, 2));
+    /// ```
+    pub fn swap(&mut self, a: usize, b: usize) -> bool {
+        if a == b || !self.contains(a) || !self.contains(b) {
+            false
+        } else {
+            self.vec.swap(a - self.offset, b - self.offset);
+            true
+        }
+    }
+
+    /// Returns the entry for the given identifier, for in-place manipulation.
+    ///
+    /// # Examples
+    ///
     /// ```
-    pub fn remove(&mut self, id: usize) -> Option<T> {
-        match id {
-            _ if id < self.min || id > self.max || !self.contains(id) => None,
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map: UMap<usize> = UMap::new();
+    /// *map.entry(5).or_default() += 2;
+    /// *map.entry(5).or_default() += 2;
+    /// assert_eq!(Some(4), map.get(5));
+    /// ```
+    pub fn entry(&mut self, id: usize) -> Entry<'_, T> {
+        Entry { map: self, id }
+    }
+
+    /// Removes the element from the map and returns it.
+    /// Does nothing if the element with the given id is not in the map (returns `None`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c")]);
+    /// let b = map.remove(2);
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (3, "c")]));
+    /// assert_eq!(b, Some("b"))
+    /// ```
+    pub fn remove(&mut self, id: usize) -> Option<T> {
+        let removed = match id {
+            _ if id < self.min || id > self.max || !self.contains(id) => None,
             _ if self.len == 1 => {
-                let t = self.vec[id - self.offset].clone();
-                self.vec[id - self.offset] = None;
+                let t = self.vec[id - self.offset].take();
                 self.max = 0;
                 self.min = 0;
                 self.len = 0;
@@ -641,14 +1675,12 @@ where
                 t
             }
             _ if id > self.min && id < self.max => {
-                let t = self.vec[id - self.offset].clone();
-                self.vec[id - self.offset] = None;
+                let t = self.vec[id - self.offset].take();
                 self.len -= 1;
                 t
             }
             _ if id == self.min => {
-                let t = self.vec[id - self.offset].clone();
-                self.vec[id - self.offset] = None;
+                let t = self.vec[id - self.offset].take();
                 self.len -= 1;
                 self.min = (self.min..self.max)
                     .find(|&i| self.vec[i - self.offset].is_some())
@@ -656,8 +1688,7 @@ where
                 t
             }
             _ if id == self.max => {
-                let t = self.vec[id - self.offset].clone();
-                self.vec[id - self.offset] = None;
+                let t = self.vec[id - self.offset].take();
                 self.len -= 1;
                 self.max = (self.min..self.max)
                     .rev()
@@ -666,7 +1697,49 @@ where
                 t
             }
             _ => None,
-        }
+        };
+        self.check_invariants();
+        removed
+    }
+
+    /// Removes the element from the map and returns it together with its identifier,
+    /// or returns `None` if the element with the given id is not in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c")]);
+    /// assert_eq!(map.take(2), Some((2, "b")));
+    /// assert_eq!(map.take(2), None);
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (3, "c")]));
+    /// ```
+    pub fn take(&mut self, id: usize) -> Option<(usize, T)> {
+        self.remove(id).map(|value| (id, value))
+    }
+
+    /// Removes the element with the smallest identifier among those which fulfill the `predicate`,
+    /// and returns it together with its identifier. The value is moved out of the map, not cloned.
+    /// Returns `None` if no element fulfills the `predicate`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "bb"), (3, "cc")]);
+    /// assert_eq!(map.pop_where(|v| v.len() == 2), Some((2, "bb")));
+    /// assert_eq!(map.pop_where(|v| v.len() == 2), Some((3, "cc")));
+    /// assert_eq!(map.pop_where(|v| v.len() == 2), None);
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a")]));
+    /// ```
+    pub fn pop_where(&mut self, predicate: impl Fn(&T) -> bool) -> Option<(usize, T)> {
+        let id = self
+            .iter()
+            .find(|(_id, value)| predicate(value))
+            .map(|(id, _value)| id)?;
+        self.take(id)
     }
 
     // Returns the keys of the map as `USet`.
@@ -685,6 +1758,27 @@ where
         USet::from_fields(set, self.offset)
     }
 
+    /// Returns an iterator over the keys of the map, in ascending order.
+    /// Unlike [`keys`], it does not allocate a `USet`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, "a"), (2, "b"), (4, "c")]);
+    /// let mut iterator = map.keys_iter();
+    /// assert_eq!(iterator.next(), Some(1));
+    /// assert_eq!(iterator.next(), Some(2));
+    /// assert_eq!(iterator.next(), Some(4));
+    /// assert_eq!(iterator.next(), None);
+    /// ```
+    ///
+    /// [`keys`]: #method.keys
+    pub fn keys_iter(&self) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.iter().map(|(id, _value)| id)
+    }
+
     /// Removes and returns the element at position `index` within the map.
     /// Returns `None` if `index` is out of bounds.
     ///
@@ -734,6 +1828,54 @@ where
         }
     }
 
+    /// Returns the identifier at position `index` within the map, or `None` if `index`
+    /// is out of bounds. Works like [`at_index`], but skips the value.
+    ///
+    ///# Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (3, "b"), (4, "c")]);
+    /// assert_eq!(map.id_at_index(0), Some(2));
+    /// assert_eq!(map.id_at_index(1), Some(3));
+    /// assert_eq!(map.id_at_index(2), Some(4));
+    /// assert_eq!(map.id_at_index(3), None);
+    /// ```
+    ///
+    /// [`at_index`]: #method.at_index
+    pub fn id_at_index(&self, index: usize) -> Option<usize> {
+        if index >= self.len {
+            None
+        } else {
+            self.iter().nth(index).map(|(id, _value)| id)
+        }
+    }
+
+    /// Returns a reference to the value at position `index` within the map, or `None`
+    /// if `index` is out of bounds. Works like [`at_index`], but does not clone the value.
+    ///
+    ///# Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (3, "b"), (4, "c")]);
+    /// assert_eq!(map.value_at_index(0), Some(&"a"));
+    /// assert_eq!(map.value_at_index(1), Some(&"b"));
+    /// assert_eq!(map.value_at_index(2), Some(&"c"));
+    /// assert_eq!(map.value_at_index(3), None);
+    /// ```
+    ///
+    /// [`at_index`]: #method.at_index
+    pub fn value_at_index(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            None
+        } else {
+            self.iter().nth(index).map(|(_id, value)| value)
+        }
+    }
+
     /// Returns an iterator over the map.
     ///
     /// # Examples
@@ -753,7 +1895,7 @@ where
     /// assert_eq!(iterator.next(), Some((4, &String::from("c"))));
     /// assert_eq!(iterator.next(), None);
     /// ```
-    pub fn iter(&self) -> UMapIter<T> {
+    pub fn iter(&self) -> UMapIter<'_, T> {
         UMapIter {
             handle: self,
             index: 0,
@@ -761,6 +1903,61 @@ where
         }
     }
 
+    /// Collects identifiers and clones of the elements of the map into a new vector,
+    /// in the ascending order of identifiers. Unlike `Vec::from`, this method does not consume
+    /// the map, and unlike [`iter`], the result does not borrow it, so it can be used e.g.
+    /// in a loop which modifies the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b")]);
+    /// for (id, value) in map.to_pairs() {
+    ///     map.put(id + 10, value);
+    /// }
+    /// assert_eq!(map.to_pairs(), vec![(1, "a"), (2, "b"), (11, "a"), (12, "b")]);
+    /// ```
+    ///
+    /// [`iter`]: #method.iter
+    pub fn to_pairs(&self) -> Vec<(usize, T)> {
+        self.iter().map(|(id, value)| (id, value.clone())).collect()
+    }
+
+    /// Returns an iterator over the elements of the map with identifiers within the given range.
+    /// Only the part of the map's internal vector overlapping with the range is scanned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, "a"), (3, "b"), (8, "c"), (10, "d")]);
+    /// let mut iterator = map.range(2..9);
+    ///
+    /// assert_eq!(iterator.next(), Some((3, &"b")));
+    /// assert_eq!(iterator.next(), Some((8, &"c")));
+    /// assert_eq!(iterator.next(), None);
+    /// ```
+    pub fn range(&self, range: Range<usize>) -> UMapIter<'_, T> {
+        if self.is_empty() || range.start > self.max || range.end <= self.min {
+            UMapIter {
+                handle: self,
+                index: self.vec.len(),
+                rindex: 0,
+            }
+        } else {
+            let from = cmp::max(range.start, self.min) - self.offset;
+            let to = cmp::min(range.end - 1, self.max) - self.offset + 1;
+            UMapIter {
+                handle: self,
+                index: from,
+                rindex: self.vec.len() - to,
+            }
+        }
+    }
+
     /// Returns the smallest identifier in the map or None if the map is empty.
     ///
     /// ```
@@ -811,6 +2008,114 @@ where
         }
     }
 
+    /// Returns the range from the smallest to the largest identifier in the map,
+    /// or None if the map is empty.
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// assert_eq!(UMap::<&str>::new().span(), None);
+    ///
+    /// let map = UMap::from_slice(&[(3, "a"), (5, "b"), (9, "c")]);
+    /// assert_eq!(map.span(), Some(3..=9));
+    /// ```
+    pub fn span(&self) -> Option<RangeInclusive<usize>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.min..=self.max)
+        }
+    }
+
+    /// Returns the smallest identifier in the map, assuming the map is not empty.
+    /// Useful in tight loops where the map is known to be non-empty and the `Option`
+    /// returned by [`min`] would only add a branch.
+    ///
+    /// # Panics
+    ///
+    /// The map must not be empty. This is checked only in debug builds: in release builds,
+    /// the result for an empty map is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (5, "b"), (7, "c")]);
+    /// assert_eq!(2, map.min_unchecked());
+    /// ```
+    ///
+    /// [`min`]: #method.min
+    pub fn min_unchecked(&self) -> usize {
+        debug_assert!(!self.is_empty(), "min_unchecked called on an empty map");
+        self.min
+    }
+
+    /// Returns the largest identifier in the map, assuming the map is not empty.
+    /// The counterpart of [`min_unchecked`].
+    ///
+    /// # Panics
+    ///
+    /// The map must not be empty. This is checked only in debug builds: in release builds,
+    /// the result for an empty map is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (5, "b"), (7, "c")]);
+    /// assert_eq!(7, map.max_unchecked());
+    /// ```
+    ///
+    /// [`min_unchecked`]: #method.min_unchecked
+    pub fn max_unchecked(&self) -> usize {
+        debug_assert!(!self.is_empty(), "max_unchecked called on an empty map");
+        self.max
+    }
+
+    /// Returns a reference to the element with the smallest identifier in the map,
+    /// or `None` if the map is empty.
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::new();
+    /// assert_eq!(map.first(), None);
+    ///
+    /// map.put(3, "a");
+    /// map.put(1, "b");
+    /// assert_eq!(map.first(), Some(&"b"));
+    /// ```
+    pub fn first(&self) -> Option<&T> {
+        if self.is_empty() {
+            None
+        } else {
+            self.get_ref(self.min)
+        }
+    }
+
+    /// Returns a reference to the element with the largest identifier in the map,
+    /// or `None` if the map is empty.
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::new();
+    /// assert_eq!(map.last(), None);
+    ///
+    /// map.put(3, "a");
+    /// map.put(1, "b");
+    /// assert_eq!(map.last(), Some(&"a"));
+    /// ```
+    pub fn last(&self) -> Option<&T> {
+        if self.is_empty() {
+            None
+        } else {
+            self.get_ref(self.max)
+        }
+    }
+
     fn make_from_slice(slice: &[(usize, T)]) -> (usize, usize, usize, Vec<Option<T>>) {
         match slice.iter().minmax_by_key(|(ref id, _)| *id) {
             MinMaxResult::NoElements => (0, 0, 0, Vec::<Option<T>>::new()),
@@ -818,12 +2123,14 @@ where
                 (*id, *id, 1, vec![Some(value.clone()); 1])
             }
             MinMaxResult::MinMax(&(min, _), &(max, _)) => {
-                let len = slice.len();
-                let capacity = cmp::max(INITIAL_CAPACITY, max + 1 - min);
+                let capacity = Self::capacity_for(min, max);
                 let mut vec = vec![None; capacity];
-                slice
-                    .iter()
-                    .for_each(|(id, value)| vec[*id - min] = Some(value.clone()));
+                let mut len = 0usize;
+                slice.iter().for_each(|(id, value)| {
+                    if vec[*id - min].replace(value.clone()).is_none() {
+                        len += 1;
+                    }
+                });
                 (min, max, len, vec)
             }
         }
@@ -831,6 +2138,7 @@ where
 
     /// Creates a map from a slice of tuples: identifiers and values.
     /// This is the same as the `from_iter` method.
+    /// If the same identifier appears more than once, the last value wins.
     ///
     /// # Examples
     ///
@@ -855,17 +2163,118 @@ where
                 offset: min,
                 min,
                 max,
+                realloc_hook: ReallocHook::default(),
+            }
+        }
+    }
+
+    /// Creates a dense map with an element for every identifier in the range, produced by `f`.
+    /// The internal vector is allocated once. This is the map counterpart of `USet::from_range`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let squares = UMap::from_range_with(0..3, |id| id * id);
+    /// assert_eq!(squares, UMap::from_slice(&[(0, 0), (1, 1), (2, 4)]));
+    /// assert!(UMap::from_range_with(3..3, |id| id).is_empty());
+    /// ```
+    pub fn from_range_with<F: Fn(usize) -> T>(range: Range<usize>, f: F) -> Self {
+        if range.is_empty() {
+            UMap::new()
+        } else {
+            let min = range.start;
+            let max = range.end - 1;
+            let len = range.len();
+            let capacity = cmp::max(INITIAL_CAPACITY, len);
+            let mut vec = Vec::with_capacity(capacity);
+            vec.extend(range.map(|id| Some(f(id))));
+            vec.resize(capacity, None);
+            UMap {
+                vec,
+                len,
+                offset: min,
+                min,
+                max,
+                realloc_hook: ReallocHook::default(),
             }
         }
     }
 
-    fn debug_compare(self: &Self, other: &UMap<T>) {
+    /// Creates a map from two parallel slices: identifiers and values.
+    /// The value at `values[n]` is put in the map under the identifier `keys[n]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slices are of different lengths.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_keys_and_values(&[1, 2, 4], &["a", "b", "c"]);
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (2, "b"), (4, "c")]));
+    /// ```
+    pub fn from_keys_and_values(keys: &[usize], values: &[T]) -> Self {
+        assert_eq!(
+            keys.len(),
+            values.len(),
+            "keys and values must be of the same length"
+        );
+        let vec: Vec<(usize, T)> = keys.iter().cloned().zip(values.iter().cloned()).collect();
+        UMap::from_slice(&vec)
+    }
+
+    fn update_min_max(&mut self) {
+        if self.is_empty() {
+            self.offset = 0;
+            self.min = 0;
+            self.max = 0;
+        } else {
+            let offset = self.offset;
+            self.min = (self.min..=self.max)
+                .find(|&id| self.vec[id - offset].is_some())
+                .unwrap();
+            self.max = (self.min..=self.max)
+                .rev()
+                .find(|&id| self.vec[id - offset].is_some())
+                .unwrap();
+        }
+    }
+
+    // True if every element of this map is also in `other`, under the same identifier.
+    // Works like `USet::is_subset_of`: after one check of the spans, both windows cover
+    // `self.min..=self.max`, so they can be compared slot by slot.
+    fn is_submap_of(&self, other: &UMap<T>) -> bool {
+        if self.is_empty() {
+            true
+        } else if self.len > other.len || self.min < other.min || self.max > other.max {
+            false
+        } else {
+            let mine = &self.vec[self.min - self.offset..=self.max - self.offset];
+            let theirs = &other.vec[self.min - other.offset..=self.max - other.offset];
+            mine.iter()
+                .zip(theirs.iter())
+                .all(|(a, b)| a.is_none() || a == b)
+        }
+    }
+
+    // Returns the smallest identifier under which both maps hold different values.
+    fn find_conflict(&self, other: &UMap<T>) -> Option<usize> {
+        self.iter()
+            .find(|&(id, value)| other.get_ref(id).map_or(false, |v| v != value))
+            .map(|(id, _)| id)
+    }
+
+    fn debug_compare(&self, other: &UMap<T>) {
         // don't perform operation on maps if they have different elements at the same places - clearly something's messed up
-        debug_assert!(self
-            .iter()
-            .zip(other.iter())
-            .find(|&((i1, ref v1), (i2, ref v2))| i1 == i2 && v1 != v2)
-            .is_none());
+        if cfg!(debug_assertions) {
+            if let Some(id) = self.find_conflict(other) {
+                panic!("the maps hold different values under the id {}", id);
+            }
+        }
     }
 
     /// Adds all tuples in the slice to the map.
@@ -894,57 +2303,220 @@ where
     /// assert_eq!(Some("d"), map.get(5));
     /// ```
     pub fn put_all(&mut self, slice: &[(usize, T)]) {
-        if !slice.is_empty() {
-            if self.is_empty() {
-                let (min, max, len, new_vec) = UMap::make_from_slice(slice);
+        self.insert_all(slice, InsertPolicy::KeepExisting);
+    }
+
+    /// Adds all tuples in the slice to the map, just like [`put_all`], and returns `true`
+    /// if the internal vector had to be reallocated, or `false` if all the elements fit within
+    /// the current capacity.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::with_capacity(10);
+    /// assert!(!map.put_all_reporting(&[(0, "a")]));
+    /// assert!(!map.put_all_reporting(&[(3, "b"), (9, "c")]));
+    /// assert!(map.put_all_reporting(&[(20, "d")]));
+    /// assert_eq!(map, UMap::from_slice(&[(0, "a"), (3, "b"), (9, "c"), (20, "d")]));
+    /// ```
+    ///
+    /// [`put_all`]: #method.put_all
+    pub fn put_all_reporting(&mut self, slice: &[(usize, T)]) -> bool {
+        self.insert_slice(slice, InsertPolicy::KeepExisting)
+    }
+
+    /// Adds all tuples in the slice to the map, reallocating at most once.
+    /// The `policy` decides what happens when an identifier is already in the map, or appears
+    /// in the slice more than once: with [`InsertPolicy::KeepExisting`] the first value stays,
+    /// as in [`put_all`], and with [`InsertPolicy::Overwrite`] the last value wins.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b")]);
+    /// map.insert_all(&[(2, "c"), (3, "d")], InsertPolicy::KeepExisting);
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (2, "b"), (3, "d")]));
+    /// map.insert_all(&[(2, "c"), (3, "e")], InsertPolicy::Overwrite);
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (2, "c"), (3, "e")]));
+    /// ```
+    ///
+    /// [`InsertPolicy::KeepExisting`]: enum.InsertPolicy.html#variant.KeepExisting
+    /// [`InsertPolicy::Overwrite`]: enum.InsertPolicy.html#variant.Overwrite
+    /// [`put_all`]: #method.put_all
+    pub fn insert_all(&mut self, slice: &[(usize, T)], policy: InsertPolicy) {
+        self.insert_slice(slice, policy);
+    }
+
+    // Returns `true` if the map had to be reallocated.
+    fn insert_slice(&mut self, slice: &[(usize, T)], policy: InsertPolicy) -> bool {
+        let (min, max) = match slice.iter().minmax_by_key(|&(id, _)| *id) {
+            MinMaxResult::NoElements => return false,
+            MinMaxResult::OneElement(&(min, _)) => (min, min),
+            MinMaxResult::MinMax(&(min, _), &(max, _)) => (min, max),
+        };
+        self.insert_entries(
+            min,
+            max,
+            slice.iter().map(|(id, value)| (*id, value)),
+            policy,
+        )
+    }
+
+    // The common path of `insert_all` and `replace_all`: all `entries` have to be within
+    // `min..=max`. Values are cloned, and the map is reallocated at most once.
+    // Returns `true` if it was reallocated.
+    fn insert_entries<'a>(
+        &mut self,
+        min: usize,
+        max: usize,
+        entries: impl Iterator<Item = (usize, &'a T)>,
+        policy: InsertPolicy,
+    ) -> bool
+    where
+        T: 'a,
+    {
+        let insert = |vec: &mut Vec<Option<T>>, index: usize, value: &T| -> bool {
+            match vec[index] {
+                None => {
+                    vec[index] = Some(value.clone());
+                    true
+                }
+                Some(ref mut old) if policy == InsertPolicy::Overwrite => {
+                    *old = value.clone();
+                    false
+                }
+                Some(_) => false,
+            }
+        };
+
+        let fits = |map: &UMap<T>| min >= map.offset && max - map.offset < map.capacity();
+        // an empty map can move its window anywhere, as long as the entries fit in the capacity
+        if self.is_empty() && !fits(self) && max - min < self.capacity() {
+            self.offset = min;
+        }
+
+        let reallocated = if fits(self) {
+            let was_empty = self.is_empty();
+            let offset = self.offset;
+            entries.for_each(|(id, value)| {
+                if insert(&mut self.vec, id - offset, value) {
+                    self.len += 1;
+                }
+            });
+            if was_empty {
                 self.min = min;
                 self.max = max;
-                self.offset = min;
-                self.len = len;
-                self.vec = new_vec;
             } else {
-                let (min, max) = match slice.iter().minmax_by_key(|&(id, _)| *id) {
-                    MinMaxResult::NoElements => (0, 0), // should not happen1
-                    MinMaxResult::OneElement(&(min, _)) => (min, min),
-                    MinMaxResult::MinMax(&(min, _), &(max, _)) => (min, max),
-                };
-
-                if min >= self.min && max <= self.max {
-                    slice.iter().for_each(|(ref id, value)| {
-                        if self.vec[*id - self.offset].is_none() {
-                            self.vec[*id - self.offset] = Some(value.clone());
-                            self.len += 1;
-                        }
-                    })
-                } else {
-                    let new_min = cmp::min(self.min, min);
-                    let new_max = cmp::max(self.max, max);
-                    let mut new_vec = vec![None; new_max - new_min + 1];
-                    self.iter()
-                        .skip(self.min - self.offset)
-                        .take(self.max - self.min + 1)
-                        .for_each(|(id, value)| new_vec[id - new_min] = Some(value.clone()));
-                    slice.iter().for_each(|(ref id, value)| {
-                        if new_vec[*id - new_min].is_none() {
-                            new_vec[*id - new_min] = Some(value.clone());
-                            self.len += 1;
-                        }
-                    });
-                    self.min = new_min;
-                    self.offset = new_min;
-                    self.max = new_max;
-                    self.vec = new_vec;
+                self.min = cmp::min(self.min, min);
+                self.max = cmp::max(self.max, max);
+            }
+            false
+        } else if self.is_empty() {
+            let mut new_vec = vec![None; cmp::max(INITIAL_CAPACITY, max + 1 - min)];
+            let mut len = 0usize;
+            entries.for_each(|(id, value)| {
+                if insert(&mut new_vec, id - min, value) {
+                    len += 1;
+                }
+            });
+            let old_capacity = self.capacity();
+            self.min = min;
+            self.max = max;
+            self.offset = min;
+            self.len = len;
+            self.vec = new_vec;
+            self.realloc_hook.call(old_capacity, self.capacity());
+            true
+        } else {
+            let new_min = cmp::min(self.min, min);
+            let new_max = cmp::max(self.max, max);
+            let old_capacity = self.capacity();
+            let mut new_vec = vec![None; new_max - new_min + 1];
+            let offset = self.offset;
+            std::mem::take(&mut self.vec)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, value)| value.is_some())
+                .for_each(|(index, value)| new_vec[index + offset - new_min] = value);
+            entries.for_each(|(id, value)| {
+                if insert(&mut new_vec, id - new_min, value) {
+                    self.len += 1;
                 }
+            });
+            self.min = new_min;
+            self.offset = new_min;
+            self.max = new_max;
+            self.vec = new_vec;
+            self.realloc_hook.call(old_capacity, self.capacity());
+            true
+        };
+        self.check_invariants();
+        reallocated
+    }
+
+    /// Concatenates independently numbered maps into one, moving the identifiers of each map
+    /// so that they come after the largest identifier of the maps before it. Identifiers of
+    /// the first map are not moved. Returns the new map, and for each input map the offset
+    /// which was added to its identifiers. Values are cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map1 = UMap::from_slice(&[(0, "a"), (1, "b")]);
+    /// let map2 = UMap::from_slice(&[(0, "c"), (1, "d")]);
+    /// let (map, offsets) = UMap::concat_renumbered(&[map1, map2]);
+    /// assert_eq!(map, UMap::from_slice(&[(0, "a"), (1, "b"), (2, "c"), (3, "d")]));
+    /// assert_eq!(offsets, vec![0, 2]);
+    /// ```
+    pub fn concat_renumbered(maps: &[UMap<T>]) -> (UMap<T>, Vec<usize>) {
+        let mut offsets = Vec::with_capacity(maps.len());
+        let mut next = 0usize;
+        let mut min = None;
+        for map in maps {
+            offsets.push(next);
+            if !map.is_empty() {
+                min = min.or(Some(next + map.min));
+                next += map.max + 1;
+            }
+        }
+
+        match min {
+            None => (UMap::new(), offsets),
+            Some(min) => {
+                let max = next - 1;
+                let mut vec = vec![None; max + 1 - min];
+                let mut len = 0usize;
+                maps.iter().zip(offsets.iter()).for_each(|(map, &offset)| {
+                    map.iter()
+                        .for_each(|(id, value)| vec[id + offset - min] = Some(value.clone()));
+                    len += map.len;
+                });
+                let map = UMap {
+                    vec,
+                    len,
+                    offset: min,
+                    min,
+                    max,
+                    realloc_hook: ReallocHook::default(),
+                };
+                map.check_invariants();
+                (map, offsets)
             }
         }
     }
 
     /// Joins two maps of the same type, creating a new one. Values are cloned.
-    /// If one of the maps is empty, the other is cloned.
+    /// If one of the maps is empty, or all its elements are also in the other one,
+    /// the other one is cloned.
     ///
     /// # Panics
     ///
-    /// Panics if both maps contain two different values under the same identifier.
+    /// In debug builds, panics if both maps contain two different values under the same
+    /// identifier. The panic message names the identifier. Use [`join_debug`] to see
+    /// the values as well.
     ///
     /// # Examples
     /// ```
@@ -955,44 +2527,169 @@ where
     /// assert_eq!(4, map3.len());
     /// assert_eq!(map3, UMap::from_slice(&[(1, "a".to_string()), (2, "b".to_string()), (3, "c".to_string()), (4, "d".to_string())]));
     /// ```
+    ///
+    /// [`join_debug`]: #method.join_debug
     pub fn join(&self, other: &Self) -> Self {
         if self.is_empty() {
-            if other.is_empty() {
-                UMap::new()
-            } else {
-                other.clone()
-            }
+            if other.is_empty() {
+                UMap::new()
+            } else {
+                other.clone()
+            }
+        } else if other.is_empty() {
+            if self.is_empty() {
+                UMap::new()
+            } else {
+                self.clone()
+            }
+        } else if other.is_submap_of(self) {
+            self.clone()
+        } else if self.is_submap_of(other) {
+            other.clone()
+        } else {
+            self.debug_compare(other);
+            let min: usize = cmp::min(self.min, other.min);
+            let max: usize = cmp::max(self.max, other.max);
+
+            let mut vec = vec![None; max + 1 - min];
+            let mut len = 0usize;
+
+            vec.iter_mut().enumerate().for_each(|(id, value)| {
+                if self.contains(id + min) {
+                    *value = self.get(id + min);
+                    len += 1;
+                } else if other.contains(id + min) {
+                    *value = other.get(id + min);
+                    len += 1;
+                }
+            });
+
+            UMap {
+                vec,
+                len,
+                offset: min,
+                min,
+                max,
+                realloc_hook: ReallocHook::default(),
+            }
+        }
+    }
+
+    /// Works like [`join`], but checks for conflicts in all builds, and if both maps contain
+    /// different values under the same identifier, the panic message names the identifier
+    /// and both values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if both maps contain two different values under the same identifier.
+    ///
+    /// # Examples
+    /// ```should_panic
+    /// use self::uset::core::umap::*;
+    /// let map1 = UMap::from_slice(&[(1, "a"), (3, "c")]);
+    /// let map2 = UMap::from_slice(&[(2, "b"), (3, "d")]);
+    /// // panics with: the maps hold different values under the id 3: "c" and "d"
+    /// map1.join_debug(&map2);
+    /// ```
+    ///
+    /// [`join`]: #method.join
+    pub fn join_debug(&self, other: &Self) -> Self
+    where
+        T: fmt::Debug,
+    {
+        if let Some(id) = self.find_conflict(other) {
+            panic!(
+                "the maps hold different values under the id {}: {:?} and {:?}",
+                id,
+                self.get_ref(id).unwrap(),
+                other.get_ref(id).unwrap()
+            );
+        }
+        self.join(other)
+    }
+
+    /// Merges two maps of the same type, creating a new one. Values are cloned.
+    /// Unlike [`join`], this method does not panic if both maps hold different values under
+    /// the same identifier. Instead, the value from `self` is taken, and the identifier is added
+    /// to the set of conflicts returned together with the merged map.
+    ///
+    /// [`join`]: #method.join
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    /// use self::uset::core::uset::*;
+    ///
+    /// let map1 = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c")]);
+    /// let map2 = UMap::from_slice(&[(2, "b"), (3, "x"), (4, "d")]);
+    /// let (merged, conflicts) = map1.merge_reporting_conflicts(&map2);
+    /// assert_eq!(merged, UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c"), (4, "d")]));
+    /// assert_eq!(conflicts, USet::from_slice(&[3]));
+    /// ```
+    pub fn merge_reporting_conflicts(&self, other: &UMap<T>) -> (UMap<T>, USet) {
+        if self.is_empty() {
+            (other.clone(), USet::new())
         } else if other.is_empty() {
-            if self.is_empty() {
-                UMap::new()
-            } else {
-                self.clone()
-            }
+            (self.clone(), USet::new())
         } else {
-            self.debug_compare(other);
             let min: usize = cmp::min(self.min, other.min);
             let max: usize = cmp::max(self.max, other.max);
 
             let mut vec = vec![None; max + 1 - min];
             let mut len = 0usize;
+            let mut conflicts = USet::new();
 
             vec.iter_mut().enumerate().for_each(|(id, value)| {
-                if self.contains(id + min) {
-                    *value = self.get(id + min);
-                    len += 1;
-                } else if other.contains(id + min) {
-                    *value = other.get(id + min);
-                    len += 1;
+                match (self.get_ref(id + min), other.get_ref(id + min)) {
+                    (Some(v1), Some(v2)) => {
+                        if v1 != v2 {
+                            conflicts.push(id + min);
+                        }
+                        *value = Some(v1.clone());
+                        len += 1;
+                    }
+                    (Some(v), None) | (None, Some(v)) => {
+                        *value = Some(v.clone());
+                        len += 1;
+                    }
+                    (None, None) => {}
                 }
             });
 
-            UMap {
+            let map = UMap {
                 vec,
                 len,
                 offset: min,
                 min,
                 max,
-            }
+                realloc_hook: ReallocHook::default(),
+            };
+            (map, conflicts)
+        }
+    }
+
+    /// Returns a read-only view of all elements with identifiers belonging to `set` which also
+    /// belong to the map. Unlike [`submap`], no value is cloned: the view borrows the map,
+    /// and only the set of common identifiers is computed.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    /// use self::uset::core::uset::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (4, "b"), (3, "c"), (5, "d")]);
+    /// let set = USet::from_slice(&[2, 3, 7]);
+    /// let view = map.submap_ref(&set);
+    /// assert_eq!(2, view.len());
+    /// assert_eq!(vec![(2, &"a"), (3, &"c")], view.iter().collect::<Vec<_>>());
+    /// ```
+    ///
+    /// [`submap`]: #method.submap
+    pub fn submap_ref(&self, set: &USet) -> UMapView<'_, T> {
+        let keys: Vec<usize> = self.entries_in(set).map(|(id, _value)| id).collect();
+        UMapView {
+            map: self,
+            keys: USet::from_slice(&keys),
         }
     }
 
@@ -1023,6 +2720,7 @@ where
                 offset: min,
                 min,
                 max,
+                realloc_hook: ReallocHook::default(),
             }
         }
     }
@@ -1073,6 +2771,83 @@ where
         vec
     }
 
+    /// Returns an iterator over the elements of the map with identifiers which also belong
+    /// to `set`, in ascending order of identifiers. Nothing is cloned or allocated.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    /// use self::uset::core::uset::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a"), (4, "b"), (3, "c"), (5, "d")]);
+    /// let set = USet::from_slice(&[1, 3, 5, 7]);
+    /// let mut iterator = map.entries_in(&set);
+    /// assert_eq!(iterator.next(), Some((3, &"c")));
+    /// assert_eq!(iterator.next(), Some((5, &"d")));
+    /// assert_eq!(iterator.next(), None);
+    /// ```
+    pub fn entries_in<'a>(&'a self, set: &'a USet) -> impl Iterator<Item = (usize, &'a T)> {
+        set.range(self.min..self.max.saturating_add(1))
+            .filter_map(move |id| self.get_ref(id).map(|value| (id, value)))
+    }
+
+    /// Returns a vector of borrowed `Cow`s of all values with identifiers belonging to `set`
+    /// which also belong to the map.
+    ///
+    /// Unlike [`retrieve`], no value is cloned up front: a value is cloned only when
+    /// the caller calls `to_mut` or `into_owned` on its `Cow`. Compared to [`retrieve_ref`],
+    /// the result can be modified in place, at the cost of a slightly bigger element type.
+    /// Use it when only a few of the retrieved values are going to be changed.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    /// use self::uset::core::uset::*;
+    /// use std::borrow::Cow;
+    ///
+    /// let map = UMap::from_slice(&[(2, "a".to_string()), (3, "c".to_string()), (4, "b".to_string())]);
+    /// let mut vec = map.retrieve_cow(&USet::from_slice(&[2, 3]));
+    /// vec[1].to_mut().push('!');
+    /// assert!(matches!(vec[0], Cow::Borrowed(_)));
+    /// assert_eq!("a", vec[0].as_str());
+    /// assert_eq!("c!", vec[1].as_str());
+    /// assert_eq!(Some("c".to_string()), map.get(3));
+    /// ```
+    ///
+    /// [`retrieve`]: #method.retrieve
+    /// [`retrieve_ref`]: #method.retrieve_ref
+    pub fn retrieve_cow(&self, set: &USet) -> Vec<Cow<'_, T>> {
+        let mut vec = Vec::with_capacity(set.len());
+        set.iter()
+            .filter_map(|id| self.get_ref(id))
+            .for_each(|value| vec.push(Cow::Borrowed(value)));
+        vec
+    }
+
+    /// Returns a `HashMap` from the values of the map to their identifiers. Values are cloned.
+    /// If the same value is stored under more than one identifier, the largest identifier wins.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "a")]);
+    /// let inverted = map.invert();
+    /// assert_eq!(Some(&3), inverted.get("a"));
+    /// assert_eq!(Some(&2), inverted.get("b"));
+    /// assert_eq!(None, inverted.get("c"));
+    /// ```
+    pub fn invert(&self) -> HashMap<T, usize>
+    where
+        T: Hash + Eq,
+    {
+        let mut inverted = HashMap::with_capacity(self.len);
+        self.iter().for_each(|(id, value)| {
+            inverted.insert(value.clone(), id);
+        });
+        inverted
+    }
+
     /// Returns a set of identifiers for which elements in the map fulfill the `predicate`.
     ///
     /// # Examples
@@ -1101,6 +2876,99 @@ where
         }
     }
 
+    /// Returns a new map with all elements of this map which fulfill the `predicate`.
+    /// Works like [`query`] followed by [`submap`], but in one pass. Values are cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "aa".to_string()), (4, "b".to_string()), (3, "cc".to_string()), (5, "d".to_string()), (11, "ee".to_string())]);
+    /// let long = map.query_entries(|v| { v.len() > 1 });
+    /// assert_eq!(long, UMap::from_slice(&[(2, "aa".to_string()), (3, "cc".to_string()), (11, "ee".to_string())]));
+    /// assert!(map.query_entries(|v| { v.len() > 2 }).is_empty());
+    /// ```
+    ///
+    /// [`query`]: #method.query
+    /// [`submap`]: #method.submap
+    pub fn query_entries(&self, predicate: impl Fn(&T) -> bool) -> Self {
+        let vec: Vec<(usize, T)> = self
+            .iter()
+            .filter(|(_id, value)| predicate(value))
+            .map(|(id, value)| (id, value.clone()))
+            .collect();
+        UMap::from_slice(&vec)
+    }
+
+    /// Returns a new map with only those elements of this map which, together with their
+    /// identifiers, fulfill the `predicate`. Values are cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c"), (4, "d")]);
+    /// let even = map.filter(|id, _v| id % 2 == 0);
+    /// assert_eq!(even, UMap::from_slice(&[(2, "b"), (4, "d")]));
+    /// assert!(map.filter(|_id, v| v.len() > 1).is_empty());
+    /// ```
+    pub fn filter(&self, predicate: impl Fn(usize, &T) -> bool) -> Self {
+        let vec: Vec<(usize, T)> = self
+            .iter()
+            .filter(|&(id, value)| predicate(id, value))
+            .map(|(id, value)| (id, value.clone()))
+            .collect();
+        UMap::from_slice(&vec)
+    }
+
+    /// Folds all elements of the map, in ascending order of identifiers, into an accumulator.
+    /// Works like `iter().fold`, but the closure takes the identifier and the value
+    /// as separate arguments.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(1, 10), (2, 20), (5, 1)]);
+    /// assert_eq!(55, map.fold(0, |sum, id, value| sum + id * value));
+    /// ```
+    pub fn fold<B>(&self, init: B, mut f: impl FnMut(B, usize, &T) -> B) -> B {
+        self.iter().fold(init, |acc, (id, value)| f(acc, id, value))
+    }
+
+    /// Returns the number of elements in the map which fulfill the `predicate`.
+    /// Works like [`query`] followed by `len`, but does not build the set.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "aa".to_string()), (4, "b".to_string()), (3, "cc".to_string()), (5, "d".to_string()), (11, "ee".to_string())]);
+    /// assert_eq!(3, map.count(|v| { v.len() > 1 }));
+    /// assert_eq!(0, map.count(|v| { v.len() > 2 }));
+    /// ```
+    ///
+    /// [`query`]: #method.query
+    pub fn count(&self, predicate: impl Fn(&T) -> bool) -> usize {
+        self.iter().filter(|(_id, value)| predicate(value)).count()
+    }
+
+    /// Returns the element with the smallest identifier which fulfills the `predicate`,
+    /// together with that identifier, or `None` if there is no such element.
+    /// Stops at the first match.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let map = UMap::from_slice(&[(2, "aa".to_string()), (4, "bbb".to_string()), (3, "c".to_string()), (5, "ddd".to_string())]);
+    /// assert_eq!(Some((4, &"bbb".to_string())), map.find(|v| { v.len() == 3 }));
+    /// assert_eq!(None, map.find(|v| { v.len() > 3 }));
+    /// ```
+    pub fn find(&self, predicate: impl Fn(&T) -> bool) -> Option<(usize, &T)> {
+        self.iter().find(|(_id, value)| predicate(value))
+    }
+
     /// A utility function making it easier to call `all` on values in the map.
     ///
     /// # Examples
@@ -1139,6 +3007,8 @@ where
     /// belonging to the given `subset`. You could achieve the same by calling [`retrieve`] on
     /// the map with `subset` as the argument, and then `all` on the iterator over the resulting
     /// vector.
+    /// Only the identifiers of `subset` within the map's span are visited, so the cost depends
+    /// on the size of `subset`, not of the map.
     ///
     /// # Examples
     /// ```
@@ -1153,15 +3023,15 @@ where
     ///
     /// [`retrieve`]: #method.retrieve
     pub fn all_in_subset(&self, subset: &USet, predicate: impl Fn(&T) -> bool) -> bool {
-        !self
-            .iter()
-            .any(|(id, value)| subset.contains(id) && !predicate(value))
+        self.entries_in(subset).all(|(_id, value)| predicate(value))
     }
 
     /// A utility method making it easier to call `any` on values in the map with identifiers
     /// belonging to the given `subset`. You could achieve the same by calling [`retrieve`] on
     /// the map with `subset` as the argument, and then `any` on the iterator over the resulting
     /// vector.
+    /// Only the identifiers of `subset` within the map's span are visited, so the cost depends
+    /// on the size of `subset`, not of the map.
     ///
     /// # Examples
     /// ```
@@ -1176,13 +3046,14 @@ where
     ///
     /// [`retrieve`]: #method.retrieve
     pub fn any_in_subset(&self, subset: &USet, predicate: impl Fn(&T) -> bool) -> bool {
-        self.iter()
-            .any(|(id, value)| subset.contains(id) && predicate(value))
+        self.entries_in(subset).any(|(_id, value)| predicate(value))
     }
 
-    /// A utility method for removing all elements with identifiers in `subset` from the map.
+    /// A utility method for removing all elements with the given identifiers from the map.
+    /// The identifiers can come from a `USet` or from any other collection or iterator of `usize`s.
+    /// Identifiers which do not belong to the map are ignored.
     /// As [`remove`] does not perform reallocation, `remove_all` is equivalent to calling `remove`
-    /// on all identifiers in `subset`. (Contrary to [`put`] and [`put_all`]).
+    /// on all the identifiers. (Contrary to [`put`] and [`put_all`]).
     ///
     /// # Examples
     /// ```
@@ -1192,16 +3063,219 @@ where
     /// let mut map = UMap::from_slice(&[(2, "aa".to_string()), (4, "b".to_string()), (3, "ccc".to_string()), (5, "d".to_string()), (11, "ee".to_string())]);
     /// let set = map.query(|v| { v.len() > 1 });
     /// map.remove_all(&set);
-    /// assert_eq!(map, UMap::from_slice(&[(4, "b".to_string()),(5, "d".to_string())]))
+    /// assert_eq!(map, UMap::from_slice(&[(4, "b".to_string()),(5, "d".to_string())]));
+    /// map.remove_all(vec![5, 6]);
+    /// assert_eq!(map, UMap::from_slice(&[(4, "b".to_string())]));
     /// ```
     ///
     /// [`remove`]: #method.remove
     /// [`put`]: #method.put
     /// [`put_all`]: #method.put_all
-    pub fn remove_all(&mut self, subset: &USet) {
-        subset.iter().for_each(|id| {
+    pub fn remove_all(&mut self, ids: impl IntoIterator<Item = usize>) {
+        ids.into_iter().for_each(|id| {
             self.remove(id);
         });
+        self.check_invariants();
+    }
+
+    /// For every identifier in `keys`, removes the element from the map if it is present,
+    /// or puts `default(id)` under it if it is absent. The map is reallocated at most once,
+    /// if `keys` extends the range of identifiers.
+    ///
+    /// Toggling the same set of keys twice with the same `default` leaves the map as it was,
+    /// except for values under the identifiers which were originally absent.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, 10), (2, 20)]);
+    /// map.toggle_keys(&USet::from_slice(&[2, 5]), |id| id * 100);
+    /// assert_eq!(map, UMap::from_slice(&[(1, 10), (5, 500)]));
+    /// ```
+    pub fn toggle_keys(&mut self, keys: &USet, default: impl Fn(usize) -> T) {
+        let (keys_min, keys_max) = match (keys.min(), keys.max()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return,
+        };
+        let (new_min, new_max) = if self.is_empty() {
+            (keys_min, keys_max)
+        } else {
+            (cmp::min(self.min, keys_min), cmp::max(self.max, keys_max))
+        };
+
+        let old_capacity = self.capacity();
+        if new_min < self.offset || new_max - self.offset >= old_capacity {
+            let new_capacity = if self.is_empty() {
+                cmp::max(INITIAL_CAPACITY, new_max - new_min + 1)
+            } else {
+                new_max - new_min + 1
+            };
+            let mut new_vec = vec![None; new_capacity];
+            let offset = self.offset;
+            std::mem::take(&mut self.vec)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, value)| value.is_some())
+                .for_each(|(index, value)| new_vec[index + offset - new_min] = value);
+            self.vec = new_vec;
+            self.offset = new_min;
+            self.realloc_hook.call(old_capacity, self.capacity());
+        }
+
+        let offset = self.offset;
+        keys.iter().for_each(|id| {
+            let slot = &mut self.vec[id - offset];
+            if slot.take().is_some() {
+                self.len -= 1;
+            } else {
+                *slot = Some(default(id));
+                self.len += 1;
+            }
+        });
+        self.min = new_min;
+        self.max = new_max;
+        self.update_min_max();
+        self.check_invariants();
+    }
+
+    /// Calls `f` on every element in the map with the identifier belonging to `subset`,
+    /// allowing to modify the elements in place. Identifiers in `subset` which do not belong
+    /// to the map are skipped.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, 10), (2, 20), (3, 30)]);
+    /// let set = USet::from_slice(&[1, 3, 4]);
+    /// map.update_all(&set, |_id, v| *v += 1);
+    /// assert_eq!(map, UMap::from_slice(&[(1, 11), (2, 20), (3, 31)]));
+    /// ```
+    pub fn update_all(&mut self, subset: &USet, f: impl Fn(usize, &mut T)) {
+        subset.iter().for_each(|id| {
+            if let Some(value) = self.get_ref_mut(id) {
+                f(id, value);
+            }
+        });
+    }
+
+    /// Returns an iterator over identifiers and mutable references to the elements, but only
+    /// for those identifiers which belong to `subset`. Identifiers in `subset` which do not belong
+    /// to the map are skipped. Elements are visited in the ascending order of their identifiers.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, 10), (2, 20), (3, 30)]);
+    /// let set = USet::from_slice(&[1, 3, 4]);
+    /// for (_id, v) in map.values_mut_in_subset(&set) {
+    ///     *v += 1;
+    /// }
+    /// assert_eq!(map, UMap::from_slice(&[(1, 11), (2, 20), (3, 31)]));
+    /// ```
+    pub fn values_mut_in_subset<'a>(
+        &'a mut self,
+        subset: &'a USet,
+    ) -> impl Iterator<Item = (usize, &'a mut T)> + 'a {
+        let (from, to) = match (subset.min(), subset.max()) {
+            (Some(min), Some(max)) if !self.is_empty() => (
+                cmp::max(min, self.min),
+                cmp::min(max, self.max).saturating_add(1),
+            ),
+            _ => (self.offset, self.offset),
+        };
+        let offset = self.offset;
+        self.vec
+            .iter_mut()
+            .enumerate()
+            .skip(from.saturating_sub(offset))
+            .take(to.saturating_sub(from))
+            .filter_map(move |(index, value)| {
+                let id = index + offset;
+                match value {
+                    Some(v) if subset.contains(id) => Some((id, v)),
+                    _ => None,
+                }
+            })
+    }
+
+    /// Retains only the elements for which `f` returns `true`, while allowing `f` to modify
+    /// the elements in place. All elements are visited exactly once, in the ascending order
+    /// of their identifiers.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, 1), (2, 3), (3, 1), (4, 2)]);
+    /// map.retain_mut(|_id, ttl| {
+    ///     *ttl -= 1;
+    ///     *ttl > 0
+    /// });
+    /// assert_eq!(map, UMap::from_slice(&[(2, 2), (4, 1)]));
+    /// ```
+    pub fn retain_mut(&mut self, mut f: impl FnMut(usize, &mut T) -> bool) {
+        if self.is_empty() {
+            return;
+        }
+
+        for id in self.min..=self.max {
+            let slot = &mut self.vec[id - self.offset];
+            if let Some(value) = slot {
+                if !f(id, value) {
+                    *slot = None;
+                    self.len -= 1;
+                }
+            }
+        }
+        self.update_min_max();
+        self.check_invariants();
+    }
+
+    /// Removes all elements which, together with their identifiers, fulfill the `predicate`,
+    /// and returns them as a new map. The rest of the elements stay in this map.
+    /// Values are moved, not cloned.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map = UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c"), (4, "d")]);
+    /// let even = map.drain_filter(|id, _v| id % 2 == 0);
+    /// assert_eq!(even, UMap::from_slice(&[(2, "b"), (4, "d")]));
+    /// assert_eq!(map, UMap::from_slice(&[(1, "a"), (3, "c")]));
+    /// ```
+    pub fn drain_filter(&mut self, predicate: impl Fn(usize, &T) -> bool) -> Self {
+        if self.is_empty() {
+            return UMap::new();
+        }
+
+        let mut drained: Vec<(usize, T)> = Vec::new();
+        for id in self.min..=self.max {
+            let slot = &mut self.vec[id - self.offset];
+            if slot.as_ref().map_or(false, |value| predicate(id, value)) {
+                drained.push((id, slot.take().unwrap()));
+            }
+        }
+
+        match (drained.first(), drained.last()) {
+            (Some(&(first, _)), Some(&(last, _))) => {
+                self.len -= drained.len();
+                self.update_min_max();
+                self.check_invariants();
+                let mut map = UMap::with_range_capacity(first, last);
+                drained
+                    .into_iter()
+                    .for_each(|(id, value)| map.put(id, value));
+                map
+            }
+            _ => UMap::new(),
+        }
     }
 
     /// Replaces the value under the identifier `id`.
@@ -1225,10 +3299,12 @@ where
         } else {
             self.put(id, value);
         }
+        self.check_invariants();
     }
 
     /// Replaces all the values with the common identifiers in the map with the ones from the `other`.
-    /// If the given identifier does not exist in the map, the [`put`] method is called.
+    /// If the given identifier does not exist in the map, the element is added, as in [`put`].
+    /// The map is reallocated at most once, as in [`insert_all`] with [`InsertPolicy::Overwrite`].
     /// Since we want to preserve the original `other` map, values are cloned.
     /// You can use this method instead of [`join`] if you are sure that it is not an error that some
     /// of the elements in both maps have different values under the same identifiers.
@@ -1248,9 +3324,155 @@ where
     /// ```
     ///
     /// [`put`]: #method.put
+    /// [`insert_all`]: #method.insert_all
+    /// [`InsertPolicy::Overwrite`]: enum.InsertPolicy.html#variant.Overwrite
     /// [`join`]: #method.join
     pub fn replace_all(&mut self, other: &UMap<T>) {
-        other.iter().for_each(|(id, v)| self.replace(id, v.clone()));
+        if !other.is_empty() {
+            self.insert_entries(other.min, other.max, other.iter(), InsertPolicy::Overwrite);
+        }
+    }
+
+    /// Puts all elements of the `other` map into this one, cloning them.
+    /// Just like [`put`], this method does not overwrite values under identifiers already present
+    /// in this map. If the `other` map extends the range of identifiers, reallocation is performed
+    /// only once, as in [`put_all`].
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map1 = UMap::from_slice(&[(2, "a"), (4, "b")]);
+    /// let map2 = UMap::from_slice(&[(1, "c"), (2, "d"), (7, "e")]);
+    /// map1.extend_from_map(&map2);
+    /// assert_eq!(map1, UMap::from_slice(&[(1, "c"), (2, "a"), (4, "b"), (7, "e")]));
+    /// ```
+    ///
+    /// [`put`]: #method.put
+    /// [`put_all`]: #method.put_all
+    pub fn extend_from_map(&mut self, other: &UMap<T>) {
+        if other.is_empty() {
+            return;
+        }
+
+        let old_capacity = self.capacity();
+        if self.is_empty() {
+            let realloc_hook = std::mem::take(&mut self.realloc_hook);
+            *self = other.clone();
+            self.realloc_hook = realloc_hook;
+            self.realloc_hook.call(old_capacity, self.capacity());
+        } else if other.min >= self.offset && other.max < self.offset + self.capacity() {
+            other.iter().for_each(|(id, value)| {
+                if self.vec[id - self.offset].is_none() {
+                    self.vec[id - self.offset] = Some(value.clone());
+                    self.len += 1;
+                }
+            });
+            self.min = cmp::min(self.min, other.min);
+            self.max = cmp::max(self.max, other.max);
+        } else {
+            let new_min = cmp::min(self.min, other.min);
+            let new_max = cmp::max(self.max, other.max);
+            let mut new_vec = vec![None; new_max - new_min + 1];
+            let offset = self.offset;
+            std::mem::take(&mut self.vec)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, value)| value.is_some())
+                .for_each(|(index, value)| new_vec[index + offset - new_min] = value);
+            other.iter().for_each(|(id, value)| {
+                if new_vec[id - new_min].is_none() {
+                    new_vec[id - new_min] = Some(value.clone());
+                    self.len += 1;
+                }
+            });
+            self.min = new_min;
+            self.offset = new_min;
+            self.max = new_max;
+            self.vec = new_vec;
+            self.realloc_hook.call(old_capacity, self.capacity());
+        }
+        self.check_invariants();
+    }
+
+    /// Moves all elements of the `other` map into this one, leaving `other` empty.
+    /// Values are moved, not cloned. Just like [`put`], this method keeps the values already
+    /// present in this map: if both maps hold a value under the same identifier, the value
+    /// from `other` is dropped. If `other` extends the range of identifiers, reallocation is
+    /// performed only once.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::umap::*;
+    ///
+    /// let mut map1 = UMap::from_slice(&[(2, "a"), (4, "b")]);
+    /// let mut map2 = UMap::from_slice(&[(1, "c"), (2, "d"), (7, "e")]);
+    /// map1.append(&mut map2);
+    /// assert_eq!(map1, UMap::from_slice(&[(1, "c"), (2, "a"), (4, "b"), (7, "e")]));
+    /// assert!(map2.is_empty());
+    /// ```
+    ///
+    /// [`put`]: #method.put
+    pub fn append(&mut self, other: &mut UMap<T>) {
+        if other.is_empty() {
+            return;
+        }
+
+        let old_capacity = self.capacity();
+        let other_capacity = other.capacity();
+        let other_hook = std::mem::take(&mut other.realloc_hook);
+        let taken = std::mem::replace(other, UMap::new());
+        // `other` keeps its hook, which reports that the vector was taken away
+        other.realloc_hook = other_hook;
+        other.realloc_hook.call(other_capacity, 0);
+        let other = taken;
+        if self.is_empty() {
+            let realloc_hook = std::mem::take(&mut self.realloc_hook);
+            *self = other;
+            self.realloc_hook = realloc_hook;
+            self.realloc_hook.call(old_capacity, self.capacity());
+            return;
+        }
+
+        let (other_min, other_max, other_offset) = (other.min, other.max, other.offset);
+        let entries = other
+            .vec
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, value)| value.map(|v| (index + other_offset, v)));
+
+        if other_min >= self.offset && other_max < self.offset + self.capacity() {
+            entries.for_each(|(id, value)| {
+                if self.vec[id - self.offset].is_none() {
+                    self.vec[id - self.offset] = Some(value);
+                    self.len += 1;
+                }
+            });
+            self.min = cmp::min(self.min, other_min);
+            self.max = cmp::max(self.max, other_max);
+        } else {
+            let new_min = cmp::min(self.min, other_min);
+            let new_max = cmp::max(self.max, other_max);
+            let mut new_vec = vec![None; new_max - new_min + 1];
+            let offset = self.offset;
+            std::mem::take(&mut self.vec)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, value)| value.is_some())
+                .for_each(|(index, value)| new_vec[index + offset - new_min] = value);
+            entries.for_each(|(id, value)| {
+                if new_vec[id - new_min].is_none() {
+                    new_vec[id - new_min] = Some(value);
+                    self.len += 1;
+                }
+            });
+            self.min = new_min;
+            self.offset = new_min;
+            self.max = new_max;
+            self.vec = new_vec;
+            self.realloc_hook.call(old_capacity, self.capacity());
+        }
+        self.check_invariants();
     }
 }
 
@@ -1259,28 +3481,27 @@ where
     T: Clone + PartialEq,
 {
     fn eq(&self, other: &Self) -> bool {
+        // only the elements are compared, so the layout of the internal vectors does not matter
         self.len == other.len
             && self.min == other.min
             && self.max == other.max
-            && self
-                .vec
-                .iter()
-                .skip(self.min - self.offset)
-                .take(self.max + 1 - self.min)
-                .zip(
-                    other
-                        .vec
-                        .iter()
-                        .skip(other.min - other.offset)
-                        .take(other.max + 1 - other.min),
-                )
-                .all(|(a, b)| *a == *b)
+            && self.iter().zip(other.iter()).all(|(a, b)| a == b)
     }
 }
 
 impl<T> Eq for UMap<T> where T: Clone + PartialEq {}
 
-impl<'a, T> Add for &'a UMap<T>
+impl<T> Hash for UMap<T>
+where
+    T: Clone + PartialEq + Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.iter().for_each(|entry| entry.hash(state));
+    }
+}
+
+impl<T> Add for &UMap<T>
 where
     T: Clone + PartialEq,
 {
@@ -1312,18 +3533,41 @@ impl<A> FromIterator<(usize, A)> for UMap<A>
 where
     A: Clone + PartialEq,
 {
+    /// Builds the map by putting elements one by one, without collecting them first.
+    /// If the iterator yields the same identifier more than once, the last value wins,
+    /// just as in [`from_slice`]. The internal vector grows in amortized steps both upwards
+    /// and downwards, so even identifiers coming in descending order do not cause
+    /// a reallocation on every put.
+    ///
+    /// [`from_slice`]: struct.UMap.html#method.from_slice
     fn from_iter<T: IntoIterator<Item = (usize, A)>>(iter: T) -> Self {
-        let vec: Vec<(usize, A)> = iter.into_iter().collect();
-        UMap::from_slice(&vec)
+        let mut map = UMap::new();
+        for (id, value) in iter {
+            if let Some(old) = map.get_ref_mut(id) {
+                *old = value;
+                continue;
+            }
+            if id < map.offset && map.capacity() > 0 {
+                let step = cmp::max(map.offset - id, map.capacity());
+                let new_offset = map.offset.saturating_sub(step);
+                map.vec.splice(
+                    0..0,
+                    std::iter::repeat_with(|| None).take(map.offset - new_offset),
+                );
+                map.offset = new_offset;
+            }
+            map.put(id, value);
+        }
+        map
     }
 }
 
-impl<T> Into<Vec<(usize, T)>> for UMap<T>
+impl<T> From<UMap<T>> for Vec<(usize, T)>
 where
     T: Clone + PartialEq,
 {
-    fn into(self) -> Vec<(usize, T)> {
-        self.iter().map(|(id, value)| (id, value.clone())).collect()
+    fn from(map: UMap<T>) -> Self {
+        map.to_pairs()
     }
 }
 
@@ -1333,10 +3577,8 @@ where
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "UMap(").unwrap();
-        for item in &self.vec {
-            if let Some(entry) = item {
-                write!(f, "{:?}", entry).unwrap();
-            }
+        for entry in self.vec.iter().flatten() {
+            write!(f, "{:?}", entry).unwrap();
         }
         write!(f, ")").unwrap();
         Ok(())
@@ -1347,9 +3589,24 @@ impl<A> Extend<(usize, A)> for UMap<A>
 where
     A: Clone + PartialEq,
 {
+    /// Puts all elements from the iterator into the map.
+    /// If the iterator reports it will yield at least [`INITIAL_CAPACITY`] elements,
+    /// they are first collected and added with [`put_all`], so the map reallocates at most once.
+    /// Otherwise the elements are put one by one. In both cases, just like with [`put`],
+    /// the first value under a given identifier is kept.
+    ///
+    /// [`INITIAL_CAPACITY`]: constant.INITIAL_CAPACITY.html
+    /// [`put_all`]: struct.UMap.html#method.put_all
+    /// [`put`]: struct.UMap.html#method.put
     fn extend<T: IntoIterator<Item = (usize, A)>>(&mut self, iter: T) {
-        for (id, value) in iter {
-            self.put(id, value);
+        let iter = iter.into_iter();
+        if iter.size_hint().0 >= INITIAL_CAPACITY {
+            let vec: Vec<(usize, A)> = iter.collect();
+            self.put_all(&vec);
+        } else {
+            for (id, value) in iter {
+                self.put(id, value);
+            }
         }
     }
 }
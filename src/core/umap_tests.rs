@@ -1,4 +1,9 @@
 #[cfg(test)]
+#[allow(
+    clippy::module_inception,
+    clippy::unnecessary_unwrap,
+    clippy::useless_vec
+)]
 mod umap_tests {
     use crate::core::umap::*;
     use crate::core::uset::*;
@@ -143,4 +148,1137 @@ mod umap_tests {
         }
         assert_eq!(Some(&"d"), map.get_ref(1));
     }
+
+    #[test]
+    fn should_count_matching_values() {
+        let map = umap![(1, "a"), (2, "bb"), (4, "ccc"), (7, "dd")];
+        assert_eq!(3, map.count(|v| v.len() > 1));
+        assert_eq!(map.query(|v| v.len() > 1).len(), map.count(|v| v.len() > 1));
+
+        let empty: UMap<&str> = UMap::new();
+        assert_eq!(0, empty.count(|v| v.len() > 1));
+    }
+
+    #[test]
+    fn should_iterate_keys_without_set() {
+        let map = umap![(3, "a"), (5, "b"), (8, "c")];
+        let keys: Vec<usize> = map.keys_iter().collect();
+        assert_eq!(vec![3, 5, 8], keys);
+        let set_keys: Vec<usize> = map.keys().iter().collect();
+        assert_eq!(set_keys, keys);
+
+        let empty: UMap<&str> = UMap::new();
+        assert_eq!(0, empty.keys_iter().count());
+    }
+
+    #[test]
+    fn should_make_map_from_keys_and_values() {
+        let map = UMap::from_keys_and_values(&[1, 2, 4], &["a", "b", "c"]);
+        assert_eq!(3, map.len());
+        assert_eq!(Some("a"), map.get(1));
+        assert_eq!(Some("b"), map.get(2));
+        assert_eq!(None, map.get(3));
+        assert_eq!(Some("c"), map.get(4));
+
+        let empty = UMap::<&str>::from_keys_and_values(&[], &[]);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_panic_on_keys_and_values_length_mismatch() {
+        UMap::from_keys_and_values(&[1, 2], &["a"]);
+    }
+
+    #[test]
+    fn should_put_into_reserved_range() {
+        let mut map = UMap::with_range_capacity(1000, 1015);
+        for &id in &[1007, 1000, 1015, 1003] {
+            map.put(id, id % 10);
+            assert_eq!(16, map.capacity());
+        }
+        assert_eq!(umap![(1000, 0), (1003, 3), (1007, 7), (1015, 5)], map);
+    }
+
+    #[test]
+    fn should_put_all_into_reserved_range() {
+        let mut map = UMap::with_range_capacity(1000, 1015);
+        map.put_all(&[(1007, 'a'), (1003, 'b'), (1007, 'c'), (1012, 'd')]);
+        assert_eq!(16, map.capacity());
+        assert_eq!(1000, map.offset());
+        assert_eq!(umap![(1003, 'b'), (1007, 'a'), (1012, 'd')], map);
+        assert_eq!(Some(1003), map.min());
+        assert_eq!(Some(1012), map.max());
+
+        map.put_all(&[(1000, 'e'), (1015, 'f')]);
+        assert_eq!(16, map.capacity());
+        assert_eq!(5, map.len());
+    }
+
+    #[test]
+    fn should_take() {
+        let mut map = umap![(3, "a"), (5, "b"), (8, "c")];
+        assert_eq!(Some((8, "c")), map.take(8));
+        assert_eq!(None, map.take(8));
+        assert_eq!(None, map.take(4));
+        assert_eq!(Some(5), map.max());
+        assert_eq!(2, map.len());
+    }
+
+    #[test]
+    fn should_count_with_entry_or_default() {
+        let mut counts: UMap<usize> = UMap::new();
+        for &id in &[4, 2, 4, 9, 4, 2] {
+            *counts.entry(id).or_default() += 1;
+        }
+        assert_eq!(umap![(2, 2), (4, 3), (9, 1)], counts);
+        assert_eq!(3, counts.len());
+
+        counts.entry(9).and_modify(|v| *v *= 10).or_default();
+        counts.entry(7).and_modify(|v| *v *= 10).or_default();
+        assert_eq!(umap![(2, 2), (4, 3), (7, 0), (9, 10)], counts);
+    }
+
+    #[test]
+    fn should_try_insert() {
+        let mut map = umap![(3, 30)];
+        if let Ok(v) = map.try_insert(5, 50) {
+            *v += 1;
+        }
+        assert_eq!(umap![(3, 30), (5, 51)], map);
+
+        let error = map.try_insert(3, 0).unwrap_err();
+        assert_eq!(
+            OccupiedError {
+                id: 3,
+                existing: 30,
+                value: 0
+            },
+            error
+        );
+        assert_eq!(Some(30), map.get(3));
+        assert_eq!(2, map.len());
+        assert_eq!(
+            "failed to insert 0, id 3 already holds 30",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn should_update_selected_values() {
+        let mut map = umap![(1, 1), (3, 3), (5, 5), (8, 8)];
+        let selected = map.query(|&v| v > 2);
+        map.update_all(&selected, |id, v| *v += id * 10);
+        assert_eq!(umap![(1, 1), (3, 33), (5, 55), (8, 88)], map);
+
+        map.update_all(&uset![0, 2, 100], |_id, v| *v = 0);
+        assert_eq!(umap![(1, 1), (3, 33), (5, 55), (8, 88)], map);
+    }
+
+    #[test]
+    fn should_access_ids_and_values_by_index() {
+        let map = umap![(2, "a"), (5, "b"), (9, "c")];
+        for index in 0..4 {
+            let pair = map.at_index(index);
+            assert_eq!(pair.map(|(id, _)| id), map.id_at_index(index));
+            assert_eq!(pair.map(|(_, v)| v), map.value_at_index(index).cloned());
+        }
+
+        let empty: UMap<&str> = UMap::new();
+        assert_eq!(None, empty.id_at_index(0));
+        assert_eq!(None, empty.value_at_index(0));
+    }
+
+    #[test]
+    fn should_invert() {
+        let map = umap![(1, "a".to_string()), (2, "b".to_string())];
+        let inverted = map.invert();
+        assert_eq!(2, inverted.len());
+        assert_eq!(Some(&1), inverted.get("a"));
+        assert_eq!(Some(&2), inverted.get("b"));
+        assert_eq!(None, inverted.get("c"));
+
+        let empty: UMap<String> = UMap::new();
+        assert!(empty.invert().is_empty());
+    }
+
+    #[test]
+    fn should_drain_range() {
+        let mut map = umap![(1, "a"), (3, "b"), (5, "c"), (8, "d"), (10, "e")];
+        let drained = map.drain_range(2..9);
+        assert_eq!(umap![(3, "b"), (5, "c"), (8, "d")], drained);
+        assert_eq!(3, drained.len());
+        assert_eq!(umap![(1, "a"), (10, "e")], map);
+        assert_eq!(2, map.len());
+
+        let drained = map.drain_range(10..11);
+        assert_eq!(umap![(10, "e")], drained);
+        assert_eq!(Some(1), map.max());
+
+        assert!(map.drain_range(2..20).is_empty());
+        assert!(UMap::<u8>::new().drain_range(0..10).is_empty());
+    }
+
+    #[test]
+    fn should_iterate_over_range() {
+        let map = umap![(1, 'a'), (3, 'b'), (8, 'c'), (10, 'd')];
+        assert_eq!(
+            vec![(3, &'b'), (8, &'c')],
+            map.range(2..9).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(8, &'c'), (3, &'b')],
+            map.range(2..9).rev().collect::<Vec<_>>()
+        );
+        assert_eq!(4, map.range(0..100).count());
+        assert_eq!(0, map.range(11..20).count());
+        assert_eq!(0, UMap::<char>::new().range(0..10).count());
+
+        let top = umap![(usize::MAX - 1, 'a'), (usize::MAX, 'b')];
+        assert_eq!(
+            vec![(usize::MAX - 1, &'a')],
+            top.range(0..usize::MAX).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_get_first_and_last() {
+        let mut map = umap![(2, "a"), (7, "b"), (15, "c")];
+        assert_eq!(Some(&"a"), map.first());
+        assert_eq!(Some(&"c"), map.last());
+        map.remove(2);
+        map.remove(15);
+        assert_eq!(Some(&"b"), map.first());
+        assert_eq!(Some(&"b"), map.last());
+        map.remove(7);
+        assert_eq!(None, map.first());
+        assert_eq!(None, map.last());
+    }
+
+    #[test]
+    fn should_grow_to_include_without_putting() {
+        let mut map = UMap::from_slice(&[(10, 'a'), (12, 'b')]);
+        map.grow_to_include(3);
+        map.grow_to_include(30);
+        let capacity = map.capacity();
+        assert_eq!(2, map.len());
+        assert_eq!(None, map.get(3));
+        map.put(3, 'c');
+        map.put(30, 'd');
+        assert_eq!(capacity, map.capacity());
+        assert_eq!(
+            vec![(3, 'c'), (10, 'a'), (12, 'b'), (30, 'd')],
+            Vec::from(map)
+        );
+    }
+
+    #[test]
+    fn should_merge_reporting_conflicts() {
+        let map1 = UMap::from_slice(&[(1, 'a'), (3, 'c'), (5, 'e'), (8, 'h')]);
+        let map2 = UMap::from_slice(&[(0, 'z'), (3, 'x'), (5, 'e'), (8, 'y'), (12, 'l')]);
+        let (merged, conflicts) = map1.merge_reporting_conflicts(&map2);
+        assert_eq!(
+            merged,
+            UMap::from_slice(&[(0, 'z'), (1, 'a'), (3, 'c'), (5, 'e'), (8, 'h'), (12, 'l')])
+        );
+        assert_eq!(6, merged.len());
+        assert_eq!(conflicts, USet::from_slice(&[3, 8]));
+
+        let (merged, conflicts) = map1.merge_reporting_conflicts(&UMap::new());
+        assert_eq!(merged, map1);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn should_extend_from_map_keeping_existing_values() {
+        let mut map = UMap::from_slice(&[(5, 'a'), (6, 'b'), (9, 'c')]);
+        map.extend_from_map(&UMap::from_slice(&[(6, 'x'), (7, 'd')]));
+        assert_eq!(
+            map,
+            UMap::from_slice(&[(5, 'a'), (6, 'b'), (7, 'd'), (9, 'c')])
+        );
+
+        map.extend_from_map(&UMap::from_slice(&[(2, 'e'), (9, 'y'), (20, 'f')]));
+        assert_eq!(
+            map,
+            UMap::from_slice(&[(2, 'e'), (5, 'a'), (6, 'b'), (7, 'd'), (9, 'c'), (20, 'f')])
+        );
+        assert_eq!(6, map.len());
+        assert_eq!(19, map.capacity());
+
+        let mut empty = UMap::new();
+        empty.extend_from_map(&map);
+        assert_eq!(empty, map);
+    }
+
+    #[test]
+    fn should_not_reallocate_within_reserved_range() {
+        let mut map = UMap::from_slice(&[(50, 0), (52, 0)]);
+        map.reserve_range(30, 90);
+        let capacity = map.capacity();
+        for id in &[88, 31, 67, 30, 90, 45, 51] {
+            map.put(*id, *id);
+        }
+        assert_eq!(capacity, map.capacity());
+        assert_eq!(9, map.len());
+
+        let mut empty = UMap::new();
+        empty.reserve_range(1000, 1100);
+        let capacity = empty.capacity();
+        for id in (1000..=1100).rev().step_by(7) {
+            empty.put(id, id);
+        }
+        assert_eq!(capacity, empty.capacity());
+    }
+
+    #[test]
+    fn should_reserve_range_extending_both_ends_at_once() {
+        let mut map = UMap::from_slice(&[(50, 'a'), (52, 'b')]);
+        map.reserve_range(30, 90);
+        assert_eq!(61, map.capacity());
+        assert_eq!(30, map.offset());
+        assert_eq!(umap![(50, 'a'), (52, 'b')], map);
+
+        map.reserve_range(40, 60);
+        assert_eq!(61, map.capacity());
+        assert_eq!(30, map.offset());
+    }
+
+    #[test]
+    fn should_append_moving_entries() {
+        let mut map1 = UMap::from_slice(&[(5, "a".to_string()), (9, "b".to_string())]);
+        let mut map2 = UMap::from_slice(&[(1, "c".to_string()), (12, "d".to_string())]);
+        map1.append(&mut map2);
+        assert!(map2.is_empty());
+        assert_eq!(4, map1.len());
+        assert_eq!(Some("c".to_string()), map1.get(1));
+        assert_eq!(Some("d".to_string()), map1.get(12));
+
+        let mut map3 = UMap::from_slice(&[(5, "x".to_string()), (7, "e".to_string())]);
+        map1.append(&mut map3);
+        assert!(map3.is_empty());
+        assert_eq!(5, map1.len());
+        assert_eq!(Some("a".to_string()), map1.get(5));
+        assert_eq!(Some("e".to_string()), map1.get(7));
+
+        let mut empty = UMap::new();
+        empty.append(&mut map1);
+        assert!(map1.is_empty());
+        assert_eq!(5, empty.len());
+    }
+
+    #[test]
+    fn should_retain_mut_decrementing_ttl() {
+        let mut map = UMap::from_slice(&[(3, 1), (5, 2), (6, 1), (9, 3), (12, 1)]);
+        map.retain_mut(|_id, ttl| {
+            *ttl -= 1;
+            *ttl > 0
+        });
+        assert_eq!(map, UMap::from_slice(&[(5, 1), (9, 2)]));
+        assert_eq!(2, map.len());
+
+        map.retain_mut(|_id, ttl| {
+            *ttl -= 1;
+            *ttl > 0
+        });
+        assert_eq!(map, UMap::from_slice(&[(9, 1)]));
+
+        map.retain_mut(|_id, ttl| {
+            *ttl -= 1;
+            *ttl > 0
+        });
+        assert!(map.is_empty());
+        assert_eq!(map, UMap::new());
+    }
+
+    #[test]
+    fn should_drain_filter_even_keys() {
+        let mut map = UMap::from_slice(&[(2, 'a'), (3, 'b'), (4, 'c'), (7, 'd'), (10, 'e')]);
+        let even = map.drain_filter(|id, _v| id % 2 == 0);
+        assert_eq!(even, UMap::from_slice(&[(2, 'a'), (4, 'c'), (10, 'e')]));
+        assert_eq!(3, even.len());
+        assert_eq!(map, UMap::from_slice(&[(3, 'b'), (7, 'd')]));
+        assert_eq!(2, map.len());
+
+        let none = map.drain_filter(|id, _v| id % 2 == 0);
+        assert!(none.is_empty());
+        assert_eq!(2, map.len());
+
+        let all = map.drain_filter(|_id, _v| true);
+        assert_eq!(all, UMap::from_slice(&[(3, 'b'), (7, 'd')]));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn should_mutate_values_in_subset_only() {
+        let mut map = UMap::from_slice(&[(2, 1), (4, 1), (6, 1), (8, 1), (10, 1)]);
+        let subset = USet::from_slice(&[0, 4, 5, 8, 20]);
+        let visited: Vec<usize> = map
+            .values_mut_in_subset(&subset)
+            .map(|(id, v)| {
+                *v += id;
+                id
+            })
+            .collect();
+        assert_eq!(vec![4, 8], visited);
+        assert_eq!(
+            map,
+            UMap::from_slice(&[(2, 1), (4, 5), (6, 1), (8, 9), (10, 1)])
+        );
+
+        assert_eq!(0, map.values_mut_in_subset(&USet::new()).count());
+        assert_eq!(
+            0,
+            map.values_mut_in_subset(&USet::from_slice(&[30, 40]))
+                .count()
+        );
+    }
+
+    fn extend_by_put(map: &mut UMap<u8>, entries: &[(usize, u8)]) {
+        for &(id, value) in entries {
+            map.put(id, value);
+        }
+    }
+
+    #[test]
+    fn should_extend_like_put() {
+        let entries: Vec<(usize, u8)> = (0..100)
+            .rev()
+            .chain(50..150)
+            .map(|id| (id, (id % 7) as u8))
+            .collect();
+        let mut m1 = UMap::from_slice(&[(60, 100), (200, 200)]);
+        m1.extend(entries.clone());
+        let mut m2 = UMap::from_slice(&[(60, 100), (200, 200)]);
+        extend_by_put(&mut m2, &entries);
+        assert_eq!(m1, m2);
+        assert_eq!(151, m1.len());
+        assert_eq!(Some(100), m1.get(60));
+
+        let mut m3 = UMap::new();
+        m3.extend(vec![
+            (3, 1),
+            (3, 2),
+            (1, 3),
+            (1, 4),
+            (2, 5),
+            (2, 6),
+            (3, 7),
+            (1, 8),
+        ]);
+        assert_eq!(m3, UMap::from_slice(&[(1, 3), (2, 5), (3, 1)]));
+        assert_eq!(3, m3.len());
+    }
+
+    quickcheck! {
+        fn extend_same_as_put(initial: Vec<(usize, u8)>, entries: Vec<(usize, u8)>) -> bool {
+            let mut m1 = UMap::new();
+            extend_by_put(&mut m1, &initial);
+            let mut m2 = m1.clone();
+            m1.extend(entries.clone());
+            extend_by_put(&mut m2, &entries);
+            m1 == m2 && m1.len() == m2.len() && m1.iter().eq(m2.iter())
+        }
+    }
+
+    #[test]
+    fn should_compute_capacity_like_from_slice() {
+        for &(min, max) in &[(0, 1), (3, 10), (5, 12), (5, 13), (1000, 1200)] {
+            let map = UMap::from_slice(&[(min, 'a'), (max, 'b')]);
+            assert_eq!(UMap::<char>::capacity_for(min, max), map.capacity());
+        }
+    }
+
+    #[test]
+    fn should_not_waste_capacity_after_shrink_to_fit() {
+        let mut map = UMap::with_capacity(100);
+        for &(id, value) in &[(40, 'a'), (45, 'b'), (60, 'c')] {
+            map.put(id, value);
+        }
+        map.remove(60);
+        assert_eq!(94, map.wasted_capacity());
+        map.shrink_to_fit();
+        assert_eq!(0, map.wasted_capacity());
+        assert_eq!(6, map.capacity());
+        assert_eq!(0, UMap::<char>::new().wasted_capacity());
+    }
+
+    #[test]
+    fn should_compact() {
+        let mut map = UMap::with_capacity(100);
+        for &(id, value) in &[(40, 'a'), (45, 'b'), (60, 'c')] {
+            map.put(id, value);
+        }
+        map.compact();
+        assert_eq!(21, map.capacity());
+        assert_eq!(0, map.wasted_capacity());
+        assert_eq!(map, UMap::from_slice(&[(40, 'a'), (45, 'b'), (60, 'c')]));
+        assert_eq!(
+            vec![(40, &'a'), (45, &'b'), (60, &'c')],
+            map.iter().collect::<Vec<(usize, &char)>>()
+        );
+
+        // with offset == min, ids up to min + capacity - 1 do not need reallocation
+        map.remove(60);
+        map.put(60, 'd');
+        assert_eq!(21, map.capacity());
+    }
+
+    #[test]
+    fn should_get_many_mut() {
+        let mut map = UMap::from_slice(&[(2, 1), (5, 2), (7, 3), (9, 4)]);
+        if let Some([a, b, c]) = map.get_many_mut([9, 2, 7]) {
+            *a *= 10;
+            *b *= 10;
+            *c *= 10;
+        } else {
+            panic!("expected three references");
+        }
+        assert_eq!(map, UMap::from_slice(&[(2, 10), (5, 2), (7, 30), (9, 40)]));
+
+        assert!(map.get_many_mut([2, 5, 2]).is_none());
+        assert!(map.get_many_mut([2, 6, 7]).is_none());
+        assert!(map.get_many_mut([100]).is_none());
+        assert!(UMap::<u8>::new().get_many_mut([0]).is_none());
+
+        let mut adjacent = UMap::from_slice(&[(3, 'a'), (4, 'b'), (5, 'c')]);
+        if let Some([c, a, b]) = adjacent.get_many_mut([5, 3, 4]) {
+            std::mem::swap(a, c);
+            *b = 'x';
+        } else {
+            panic!("expected three references");
+        }
+        assert_eq!(adjacent, UMap::from_slice(&[(3, 'c'), (4, 'x'), (5, 'a')]));
+        assert_eq!(Some([]), adjacent.get_many_mut([]));
+    }
+
+    #[test]
+    fn should_find_ceiling_and_floor_entries_in_gaps() {
+        let map = UMap::from_slice(&[(10, 'a'), (20, 'b'), (21, 'c'), (40, 'd')]);
+        assert_eq!(Some((20, &'b')), map.ceiling_entry(11));
+        assert_eq!(Some((40, &'d')), map.ceiling_entry(22));
+        assert_eq!(Some((10, &'a')), map.floor_entry(19));
+        assert_eq!(Some((21, &'c')), map.floor_entry(39));
+        assert_eq!(Some((21, &'c')), map.ceiling_entry(21));
+        assert_eq!(Some((21, &'c')), map.floor_entry(21));
+        assert_eq!(None, map.ceiling_entry(41));
+        assert_eq!(None, map.floor_entry(9));
+
+        let empty = UMap::<char>::new();
+        assert_eq!(None, empty.ceiling_entry(0));
+        assert_eq!(None, empty.floor_entry(0));
+    }
+
+    #[test]
+    fn should_remove_all_keys_from_vec() {
+        let mut map = UMap::from_slice(&[(1, 'a'), (2, 'b'), (4, 'c'), (7, 'd'), (9, 'e')]);
+        map.remove_all(vec![2, 3, 9, 20]);
+        assert_eq!(map, UMap::from_slice(&[(1, 'a'), (4, 'c'), (7, 'd')]));
+        assert_eq!(3, map.len());
+
+        map.remove_all(&USet::from_slice(&[1]));
+        map.remove_all(5..8);
+        assert_eq!(map, UMap::from_slice(&[(4, 'c')]));
+    }
+
+    quickcheck! {
+        fn invariants_hold_after_random_operations(ops: Vec<(u8, usize)>) -> bool {
+            let mut map = UMap::new();
+            for (op, arg) in ops {
+                let id = arg % 200;
+                match op % 8 {
+                    0 | 1 => map.put(id, op),
+                    2 => {
+                        map.remove(id);
+                    }
+                    3 => {
+                        let drained = map.drain(id % 10);
+                        drained.check_invariants();
+                    }
+                    4 => {
+                        let drained = map.drain_range(id..id + 20);
+                        drained.check_invariants();
+                    }
+                    5 => map.put_all(&[(id, op), (id / 2, op), (id + 7, op)]),
+                    6 => map.replace(id, op),
+                    _ => map.retain_mut(|key, _value| key % 3 != id % 3),
+                }
+                map.check_invariants();
+            }
+            true
+        }
+    }
+
+    #[test]
+    fn should_not_panic_on_get_from_empty_map() {
+        let map = UMap::<u8>::new();
+        assert_eq!(None, map.get(0));
+        assert_eq!(None, map.get_ref(0));
+        assert!(!map.contains(0));
+    }
+
+    #[test]
+    fn should_drain_iter_moving_values() {
+        use std::rc::Rc;
+        let mut map = UMap::new();
+        for (id, name) in &[(2, "a"), (5, "b"), (6, "c"), (9, "d")] {
+            map.put(*id, Rc::new(name.to_string()));
+        }
+        {
+            let mut drain = map.drain_iter();
+            let (id, value) = drain.next().unwrap();
+            assert_eq!(2, id);
+            assert_eq!(1, Rc::strong_count(&value));
+            let (id, value) = drain.next().unwrap();
+            assert_eq!(5, id);
+            assert_eq!("b", value.as_str());
+        }
+        assert_eq!(2, map.len());
+        assert_eq!(Some(6), map.min());
+        assert!(!map.contains(2));
+        assert!(!map.contains(5));
+
+        let rest: Vec<usize> = map.drain_iter().map(|(id, _)| id).collect();
+        assert_eq!(vec![6, 9], rest);
+        assert!(map.is_empty());
+    }
+
+    quickcheck! {
+        fn equal_regardless_of_construction(raw: Vec<(usize, u8)>) -> bool {
+            let raw: Vec<(usize, u8)> = raw.into_iter().map(|(id, v)| (id % 200 + 1, v)).collect();
+            let unique: std::collections::BTreeMap<usize, u8> = raw.iter().cloned().collect();
+            let entries: Vec<(usize, u8)> = unique.iter().map(|(&id, &v)| (id, v)).collect();
+
+            let m1 = UMap::from_slice(&entries);
+            let mut m2 = UMap::new();
+            m2.put_all(&entries);
+            let mut m3 = UMap::new();
+            if let Some(&max) = unique.keys().last() {
+                for id in 1..=max {
+                    m3.push(*unique.get(&id).unwrap_or(&0));
+                }
+                m3.remove_all((1..=max).filter(|id| !unique.contains_key(id)));
+            }
+            let m4 = UMap::from_slice(&raw);
+
+            m1 == m2 && m2 == m3 && m1 == m3 && m1 == m4
+                && m1.len() == entries.len()
+                && m3.len() == entries.len()
+                && m4.len() == entries.len()
+        }
+    }
+
+    #[cfg(feature = "realloc-hook")]
+    #[test]
+    fn should_report_reallocations_to_hook() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let mut map = UMap::new();
+        map.on_realloc(move |old, new| recorded.lock().unwrap().push((old, new)));
+
+        map.put(10, "a");
+        map.put(12, "b");
+        map.put(20, "c");
+        map.put(5, "d");
+        map.put_all(&[(1, "e"), (30, "f")]);
+        assert_eq!(
+            vec![(0, INITIAL_CAPACITY), (8, 11), (11, 16), (16, 30)],
+            *events.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn should_join_with_submap_without_reallocating() {
+        let mut big = UMap::with_capacity(100);
+        big.put(1, "a");
+        big.put(2, "b");
+        big.put(3, "c");
+        big.put(4, "d");
+        let small = UMap::from_slice(&[(2, "b"), (4, "d")]);
+
+        let joined = big.join(&small);
+        assert_eq!(big, joined);
+        assert_eq!(100, joined.capacity());
+
+        let joined = small.join(&big);
+        assert_eq!(big, joined);
+        assert_eq!(100, joined.capacity());
+
+        let other = UMap::from_slice(&[(2, "b"), (6, "f")]);
+        let joined = big.join(&other);
+        assert_eq!(
+            UMap::from_slice(&[(1, "a"), (2, "b"), (3, "c"), (4, "d"), (6, "f")]),
+            joined
+        );
+        assert_eq!(6, joined.capacity());
+    }
+
+    #[test]
+    fn should_summarize_map() {
+        let map: UMap<usize> = vec![3, 4, 5, 10, 20, 21]
+            .into_iter()
+            .map(|id| (id, id))
+            .collect();
+        assert_eq!(
+            "UMap{len=6, span=3..=21, runs=3, density=0.32}",
+            map.summary()
+        );
+        assert_eq!(
+            "UMap{len=0, span=none, runs=0, density=0.00}",
+            UMap::<usize>::new().summary()
+        );
+    }
+
+    fn hash_of(map: &UMap<u8>) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        map.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn should_be_equal_with_different_vector_sizes() {
+        let mut m1 = UMap::with_capacity(100);
+        m1.put(20, 2u8);
+        m1.put(50, 5);
+        m1.put(60, 6);
+        let m2 = UMap::from_slice(&[(20, 2u8), (50, 5), (60, 6)]);
+        assert_eq!(100, m1.capacity());
+        assert_eq!(41, m2.capacity());
+        assert_eq!(m1, m2);
+        assert_eq!(hash_of(&m1), hash_of(&m2));
+
+        let m3 = UMap::from_slice(&[(20, 2u8), (50, 5), (60, 7)]);
+        assert_ne!(m1, m3);
+    }
+
+    quickcheck! {
+        fn eq_consistent_with_hash(a: Vec<(u8, u8)>, b: Vec<(u8, u8)>) -> bool {
+            let a: Vec<(usize, u8)> = a.into_iter().map(|(id, v)| (id as usize % 16, v % 2)).collect();
+            let b: Vec<(usize, u8)> = b.into_iter().map(|(id, v)| (id as usize % 16, v % 2)).collect();
+            let m1 = UMap::from_slice(&a);
+            let mut m2 = UMap::with_capacity(64);
+            m2.put_all(&b);
+            let mut m3 = UMap::with_capacity(64);
+            m1.iter().rev().for_each(|(id, &v)| m3.put(id, v));
+            (m1 != m2 || hash_of(&m1) == hash_of(&m2)) && m1 == m3 && hash_of(&m1) == hash_of(&m3)
+        }
+    }
+
+    #[test]
+    fn should_retrieve_borrowed_cows() {
+        use std::borrow::Cow;
+
+        let map = UMap::from_slice(&[(1, 10), (2, 20), (5, 50)]);
+        let mut values = map.retrieve_cow(&uset![1, 3, 5]);
+        assert_eq!(2, values.len());
+        assert!(values.iter().all(|value| matches!(value, Cow::Borrowed(_))));
+
+        *values[0].to_mut() += 1;
+        assert!(matches!(values[0], Cow::Owned(11)));
+        assert!(matches!(values[1], Cow::Borrowed(&50)));
+        assert_eq!(Some(10), map.get(1));
+    }
+
+    #[test]
+    fn should_collect_with_duplicates_last_value_wins() {
+        let map: UMap<&str> = vec![(5, "a"), (3, "b"), (5, "c"), (1, "d"), (3, "e")]
+            .into_iter()
+            .collect();
+        assert_eq!(3, map.len());
+        assert_eq!(Some("c"), map.get(5));
+        assert_eq!(Some("e"), map.get(3));
+        assert_eq!(Some("d"), map.get(1));
+        assert_eq!(umap![(1, "d"), (3, "e"), (5, "c")], map);
+    }
+
+    quickcheck! {
+        fn from_iter_same_as_from_slice(entries: Vec<(u16, u8)>) -> bool {
+            let entries: Vec<(usize, u8)> = entries.into_iter().map(|(id, v)| (id as usize, v)).collect();
+            let collected: UMap<u8> = entries.iter().cloned().collect();
+            let expected = UMap::from_slice(&entries);
+            collected.check_invariants();
+            collected == expected && collected.len() == expected.len()
+        }
+    }
+
+    #[test]
+    fn should_swap_values() {
+        let mut map = umap![
+            (2, String::from("two")),
+            (5, String::from("five")),
+            (9, String::from("nine"))
+        ];
+        assert!(map.swap(9, 2));
+        assert_eq!(Some(String::from("nine")), map.get(2));
+        assert_eq!(Some(String::from("two")), map.get(9));
+        assert_eq!(3, map.len());
+
+        assert!(!map.swap(5, 6));
+        assert!(!map.swap(0, 5));
+        assert!(!map.swap(5, 5));
+        assert_eq!(Some(String::from("five")), map.get(5));
+    }
+
+    #[test]
+    fn should_return_min_and_max_unchecked() {
+        let mut map = umap![(4, "a"), (9, "b"), (13, "c")];
+        assert_eq!(4, map.min_unchecked());
+        assert_eq!(13, map.max_unchecked());
+        map.put(20, "d");
+        assert_eq!(20, map.max_unchecked());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "max_unchecked called on an empty map")]
+    fn should_panic_on_max_unchecked_of_empty_map() {
+        UMap::<u8>::new().max_unchecked();
+    }
+
+    #[test]
+    fn should_iterate_entries_in_set() {
+        let map = umap![(1, 10), (4, 40), (6, 60), (9, 90), (12, 120)];
+        let set = uset![0, 4, 5, 6, 12, 30];
+        let entries: Vec<(usize, &i32)> = map.entries_in(&set).collect();
+        assert_eq!(vec![(4, &40), (6, &60), (12, &120)], entries);
+
+        let empty_set = USet::new();
+        assert_eq!(0, map.entries_in(&empty_set).count());
+        assert_eq!(0, UMap::<i32>::new().entries_in(&set).count());
+    }
+
+    #[test]
+    fn should_insert_all_with_policy() {
+        let base = umap![(2, "a"), (4, "b"), (6, "c")];
+        let entries = [(1, "x"), (4, "y"), (6, "z"), (9, "w"), (9, "v")];
+
+        let mut kept = base.clone();
+        kept.insert_all(&entries, InsertPolicy::KeepExisting);
+        assert_eq!(
+            umap![(1, "x"), (2, "a"), (4, "b"), (6, "c"), (9, "w")],
+            kept
+        );
+        assert_eq!(5, kept.len());
+
+        let mut overwritten = base.clone();
+        overwritten.insert_all(&entries, InsertPolicy::Overwrite);
+        assert_eq!(
+            umap![(1, "x"), (2, "a"), (4, "y"), (6, "z"), (9, "v")],
+            overwritten
+        );
+        assert_eq!(5, overwritten.len());
+
+        let mut within = base.clone();
+        within.insert_all(&[(4, "y"), (5, "q")], InsertPolicy::Overwrite);
+        assert_eq!(umap![(2, "a"), (4, "y"), (5, "q"), (6, "c")], within);
+
+        let mut empty = UMap::new();
+        empty.insert_all(&[(3, "a"), (3, "b")], InsertPolicy::Overwrite);
+        assert_eq!(umap![(3, "b")], empty);
+        assert_eq!(1, empty.len());
+
+        let mut replaced = base.clone();
+        replaced.replace_all(&umap![(1, "x"), (4, "y")]);
+        assert_eq!(umap![(1, "x"), (2, "a"), (4, "y"), (6, "c")], replaced);
+    }
+
+    #[test]
+    fn should_build_from_range_with() {
+        let squares = UMap::from_range_with(0..3, |i| i * i);
+        assert_eq!(umap![(0, 0), (1, 1), (2, 4)], squares);
+        assert_eq!(3, squares.len());
+
+        let labels = UMap::from_range_with(100..120, |i| i.to_string());
+        assert_eq!(20, labels.len());
+        assert_eq!(20, labels.capacity());
+        assert_eq!(Some(100), labels.min());
+        assert_eq!(Some(119), labels.max());
+        assert_eq!(Some(String::from("111")), labels.get(111));
+    }
+
+    #[test]
+    fn should_concat_renumbered() {
+        let first = umap![(0, "a"), (1, "b")];
+        let second = umap![(0, "c"), (1, "d")];
+        let (map, offsets) = UMap::concat_renumbered(&[first, second.clone()]);
+        assert_eq!(vec![0, 2], offsets);
+        assert_eq!(4, map.len());
+        second
+            .iter()
+            .for_each(|(id, value)| assert_eq!(Some(*value), map.get(id + offsets[1])));
+
+        let (map, offsets) =
+            UMap::concat_renumbered(&[umap![(3, "a")], UMap::new(), umap![(1, "b"), (5, "c")]]);
+        assert_eq!(vec![0, 4, 4], offsets);
+        assert_eq!(umap![(3, "a"), (5, "b"), (9, "c")], map);
+
+        let (map, offsets) = UMap::<&str>::concat_renumbered(&[]);
+        assert!(map.is_empty());
+        assert!(offsets.is_empty());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "different values under the id 3")]
+    fn should_name_conflicting_id_when_joining() {
+        let map1 = umap![(1, "a"), (3, "c"), (5, "e")];
+        let map2 = umap![(2, "b"), (3, "x"), (6, "f")];
+        map1.join(&map2);
+    }
+
+    #[test]
+    #[should_panic(expected = "different values under the id 3: \"c\" and \"x\"")]
+    fn should_name_conflicting_values_when_joining_with_debug() {
+        let map1 = umap![(1, "a"), (3, "c"), (5, "e")];
+        let map2 = umap![(2, "b"), (3, "x"), (6, "f")];
+        map1.join_debug(&map2);
+    }
+
+    #[test]
+    fn should_reset_to_new_window_reusing_buffer() {
+        let mut map = UMap::with_capacity(64);
+        (0..20).for_each(|id| map.put(id, id * 2));
+        map.reset_to(1000, 1030);
+        assert!(map.is_empty());
+        assert_eq!(64, map.capacity());
+        map.put(1000, 1);
+        map.put(1030, 2);
+        assert_eq!(umap![(1000, 1), (1030, 2)], map);
+        assert_eq!(64, map.capacity());
+
+        map.reset_to(0, 99);
+        assert!(map.is_empty());
+        assert_eq!(100, map.capacity());
+    }
+
+    #[test]
+    fn should_verify_len() {
+        let mut map = umap![(1, "a"), (2, "b"), (2, "c"), (5, "d")];
+        assert!(map.verify_len());
+        map.put_all(&[(5, "e"), (7, "f"), (7, "g")]);
+        map.remove(1);
+        assert!(map.verify_len());
+        assert!(UMap::<u8>::new().verify_len());
+
+        map.vec[2] = Some("x");
+        assert!(!map.verify_len());
+    }
+
+    #[test]
+    fn should_check_all_and_any_in_subset() {
+        let map = umap![
+            (2, "aa".to_string()),
+            (4, "b".to_string()),
+            (3, "ccc".to_string()),
+            (5, "d".to_string()),
+            (11, "ee".to_string())
+        ];
+        let set = map.query(|v| v.len() > 1);
+        assert!(map.all_in_subset(&set, |v| v.len() > 1));
+        assert!(!map.all_in_subset(&set, |v| v.len() == 2));
+        assert!(!map.any_in_subset(&set, |v| v.len() == 1));
+        assert!(map.any_in_subset(&set, |v| v.len() == 3));
+
+        // identifiers outside the map are ignored
+        let wide = uset![0, 3, 100];
+        assert!(map.all_in_subset(&wide, |v| v.len() == 3));
+        assert!(!map.any_in_subset(&wide, |v| v.len() == 1));
+        assert!(map.all_in_subset(&USet::new(), |_| false));
+        assert!(!map.any_in_subset(&USet::new(), |_| true));
+    }
+
+    #[test]
+    fn should_pop_first_matching_value() {
+        let mut map = umap![
+            (1, String::from("ab")),
+            (4, String::from("abc")),
+            (6, String::from("x")),
+            (9, String::from("xyz"))
+        ];
+        assert_eq!(
+            Some((4, String::from("abc"))),
+            map.pop_where(|v| v.len() == 3)
+        );
+        assert!(!map.contains(4));
+        assert_eq!(3, map.len());
+        assert_eq!(
+            Some((9, String::from("xyz"))),
+            map.pop_where(|v| v.len() == 3)
+        );
+        assert_eq!(None, map.pop_where(|v| v.len() == 3));
+        assert_eq!(umap![(1, String::from("ab")), (6, String::from("x"))], map);
+    }
+
+    #[test]
+    fn should_view_submap_without_cloning() {
+        let map = umap![
+            (1, String::from("one")),
+            (2, String::from("two")),
+            (3, String::from("three")),
+            (8, String::from("eight"))
+        ];
+        let set = uset![2, 3, 5, 8];
+        let view = map.submap_ref(&set);
+        assert_eq!(3, view.len());
+        assert!(!view.is_empty());
+        assert_eq!(&uset![2, 3, 8], view.keys());
+        assert!(std::ptr::eq(
+            map.get_ref(3).unwrap(),
+            view.get_ref(3).unwrap()
+        ));
+        assert_eq!(None, view.get_ref(1));
+        assert_eq!(None, view.get_ref(5));
+
+        let ids: Vec<usize> = view.iter().map(|(id, _)| id).collect();
+        assert_eq!(vec![2, 3, 8], ids);
+        assert!(view.all(|v| v.len() >= 3));
+        assert!(view.any(|v| v == "eight"));
+        assert!(!view.any(|v| v == "one"));
+
+        assert!(map.submap_ref(&USet::new()).is_empty());
+    }
+
+    #[test]
+    fn should_fill_gaps_with_default() {
+        let mut map = umap![(1, "a"), (3, "c")];
+        map.fill_gaps("b");
+        assert_eq!(umap![(1, "a"), (2, "b"), (3, "c")], map);
+        assert_eq!(3, map.len());
+        assert_eq!(Some(&"b"), map.get_ref(2));
+
+        let mut empty: UMap<&str> = UMap::new();
+        empty.fill_gaps("b");
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn should_return_occupied_window_only_for_contiguous_maps() {
+        let mut map = umap![(3, 30), (4, 40), (5, 50)];
+        map.put(10, 100);
+        map.remove(10);
+        assert!(map.capacity() > 3);
+        assert_eq!(
+            Some(&[Some(30), Some(40), Some(50)][..]),
+            map.occupied_window()
+        );
+
+        let mut gapped = umap![(3, 30), (5, 50)];
+        assert_eq!(None, gapped.occupied_window());
+        gapped.fill_gaps(0);
+        assert_eq!(
+            Some(&[Some(30), Some(0), Some(50)][..]),
+            gapped.occupied_window()
+        );
+
+        assert_eq!(None, UMap::<i32>::new().occupied_window());
+    }
+
+    #[test]
+    fn should_toggle_keys_back_to_original_state() {
+        let original = umap![(2, 0), (4, 0), (6, 0)];
+        let keys = uset![1, 4, 6, 9];
+        let mut map = original.clone();
+        map.toggle_keys(&keys, |_| 0);
+        assert_eq!(umap![(1, 0), (2, 0), (9, 0)], map);
+        map.toggle_keys(&keys, |_| 0);
+        assert_eq!(original, map);
+        assert_eq!(3, map.len());
+        assert_eq!(Some(2), map.min());
+        assert_eq!(Some(6), map.max());
+
+        let mut empty: UMap<usize> = UMap::new();
+        empty.toggle_keys(&keys, |id| id);
+        assert_eq!(umap![(1, 1), (4, 4), (6, 6), (9, 9)], empty);
+        empty.toggle_keys(&keys, |id| id);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn should_collect_to_pairs_like_iter() {
+        let map = umap![(3, "c"), (5, "e"), (100, "z")];
+        let from_iter: Vec<(usize, &str)> = map.iter().map(|(id, v)| (id, *v)).collect();
+        assert_eq!(from_iter, map.to_pairs());
+        assert_eq!(vec![(3, "c"), (5, "e"), (100, "z")], map.to_pairs());
+        assert!(UMap::<&str>::new().to_pairs().is_empty());
+    }
+
+    #[test]
+    fn should_report_reallocation_in_put_all() {
+        let mut map = UMap::with_capacity(16);
+        map.put(4, "a");
+        let capacity = map.capacity();
+        assert!(!map.put_all_reporting(&[(6, "b"), (15, "c")]));
+        assert_eq!(capacity, map.capacity());
+        assert_eq!(umap![(4, "a"), (6, "b"), (15, "c")], map);
+        assert!(!map.put_all_reporting(&[]));
+        assert!(map.put_all_reporting(&[(4 + capacity, "d")]));
+        assert_eq!(
+            umap![(4, "a"), (6, "b"), (15, "c"), (4 + capacity, "d")],
+            map
+        );
+        assert!(map.put_all_reporting(&[(1, "e")]));
+        assert_eq!(Some(1), map.min());
+    }
+
+    #[test]
+    fn should_not_report_reallocation_in_put_all_on_empty_map_with_capacity() {
+        let mut map = UMap::with_capacity(10);
+        assert!(!map.put_all_reporting(&[(1, "a"), (2, "b")]));
+        assert_eq!(10, map.capacity());
+        assert_eq!(umap![(1, "a"), (2, "b")], map);
+
+        let mut map = UMap::with_range_capacity(0, 100);
+        assert!(!map.put_all_reporting(&[(1, "a"), (2, "b"), (100, "c")]));
+        assert_eq!(101, map.capacity());
+
+        let mut map = UMap::with_capacity(10);
+        assert!(!map.put_all_reporting(&[(500, "a"), (509, "b")]));
+        assert_eq!(10, map.capacity());
+        assert!(map.put_all_reporting(&[(510, "c")]));
+
+        assert!(UMap::new().put_all_reporting(&[(1, "a")]));
+    }
+
+    #[test]
+    fn should_return_span() {
+        assert_eq!(None, UMap::<&str>::new().span());
+        assert_eq!(Some(7..=7), umap![(7, "a")].span());
+        let mut map = umap![(2, "a"), (5, "b"), (11, "c")];
+        assert_eq!(Some(2..=11), map.span());
+        map.remove(2);
+        assert_eq!(Some(5..=11), map.span());
+    }
+
+    #[cfg(feature = "realloc-hook")]
+    #[test]
+    fn should_keep_realloc_hook_in_place() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let mut map: UMap<&str> = UMap::with_capacity(4);
+        map.on_realloc(move |old, new| recorded.lock().unwrap().push((old, new)));
+
+        map.reserve_range(0, 9);
+        assert_eq!(vec![(4, 10)], *events.lock().unwrap());
+
+        let source = umap![(20, "a"), (22, "b")];
+        map.extend_from_map(&source);
+        assert_eq!(
+            (10, source.capacity()),
+            *events.lock().unwrap().last().unwrap()
+        );
+
+        let capacity = map.capacity();
+        let mut other = umap![(40, "c")];
+        map.append(&mut other);
+        assert_eq!((capacity, 21), *events.lock().unwrap().last().unwrap());
+        assert_eq!(umap![(20, "a"), (22, "b"), (40, "c")], map);
+        assert_eq!(3, events.lock().unwrap().len());
+    }
 }
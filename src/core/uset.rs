@@ -2,12 +2,27 @@
 use lazy_static::lazy_static;
 
 use std::cmp;
-use std::iter::FromIterator;
-use std::ops::Range;
-use std::ops::{Add, BitXor, Mul, Sub};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::iter::{FromIterator, Peekable};
+use std::ops::{Add, BitOr, BitOrAssign, BitXor, Mul, Sub};
+use std::ops::{Range, RangeInclusive};
 
+use super::error::UsetError;
+use super::id_arith::{IdArith, IdOverflow};
+use super::realloc_hook::ReallocHook;
 use super::umap::UMap;
 use itertools::{Itertools, MinMaxResult};
+use rand::Rng;
+
+/// Creates a `USet` with the given values.
+/// Equivalent to calling [`from_slice`].
+///
+/// [`from_slice`]: #method.from_slice
+#[allow(unused_macros)]
+macro_rules! uset {
+    ($($x:expr),*) => (USet::from_slice(&[$($x),*]))
+}
 
 /// A set of unsigned integers (usizes) implemented as a vector of booleans
 /// where `vec[n - offset] == true` means that the set contains `n`. Intended for
@@ -21,16 +36,6 @@ use itertools::{Itertools, MinMaxResult};
 /// (so `offset == min`) and `max - offset == capacity`. However, for performance
 /// purposes, if the operation does not require new allocation, the capacity might be
 /// left bigger than `max - min`.
-
-/// Creates a `USet` with the given values.
-/// Equivalent to calling [`from_slice`].
-///
-/// [`from_slice`]: #method.from_slice
-#[allow(unused_macros)]
-macro_rules! uset {
-    ($($x:expr),*) => (USet::from_slice(&vec![$($x),*]))
-}
-
 #[derive(Debug, Default, Clone)]
 pub struct USet {
     vec: Vec<bool>,
@@ -38,6 +43,8 @@ pub struct USet {
     offset: usize,
     min: usize,
     max: usize,
+    policy: GrowthPolicy,
+    realloc_hook: ReallocHook,
 }
 
 pub struct USetIter<'a> {
@@ -84,16 +91,208 @@ impl<'a> IntoIterator for &'a USet {
     }
 }
 
+pub struct USetRanges<'a> {
+    iter: Peekable<USetIter<'a>>,
+}
+
+impl<'a> Iterator for USetRanges<'a> {
+    type Item = RangeInclusive<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.iter.next()?;
+        let mut end = start;
+        while end < usize::MAX && self.iter.peek() == Some(&(end + 1)) {
+            end += 1;
+            self.iter.next();
+        }
+        Some(start..=end)
+    }
+}
+
+pub struct USetCursor<'a> {
+    iter: USetIter<'a>,
+}
+
+impl<'a> USetCursor<'a> {
+    /// Returns the value which would be returned by the next call to `next`, without consuming it.
+    pub fn peek(&self) -> Option<usize> {
+        USetIter {
+            handle: self.iter.handle,
+            index: self.iter.index,
+            rindex: self.iter.rindex,
+        }
+        .next()
+    }
+
+    /// Moves the cursor so that the next returned value is the smallest value in the set
+    /// greater than or equal to `id`. The cursor can be moved both forwards and backwards.
+    pub fn seek(&mut self, id: usize) {
+        let handle = self.iter.handle;
+        self.iter.index = cmp::min(id.saturating_sub(handle.offset), handle.vec.len());
+    }
+}
+
+impl<'a> Iterator for USetCursor<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+/// A snapshot of the values of a set, built with [`USet::build_index`], for fast positional
+/// access. It does not borrow the set, so it is not updated when the set changes: after any
+/// mutation of the set, the index has to be built again.
+///
+/// [`USet::build_index`]: struct.USet.html#method.build_index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankSelectIndex {
+    values: Vec<usize>,
+}
+
+impl RankSelectIndex {
+    /// Returns the `k`-th smallest value of the set (counting from 0), or `None` if the set
+    /// had no more than `k` values. O(1). Gives the same result as [`USet::at_index`].
+    ///
+    /// [`USet::at_index`]: struct.USet.html#method.at_index
+    pub fn select(&self, k: usize) -> Option<usize> {
+        self.values.get(k).copied()
+    }
+
+    /// Returns the number of values of the set smaller than `id`. O(log n).
+    /// If `id` belongs to the set, this is its position, i.e. `select(rank(id)) == Some(id)`.
+    pub fn rank(&self, id: usize) -> usize {
+        self.values.partition_point(|&value| value < id)
+    }
+
+    /// Returns the number of values in the index.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if the index was built from an empty set.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
 pub const INITIAL_WORKING_CAPACITY: usize = 8;
 
+/// The largest span of values, `max - min + 1`, accepted by `USet::try_from_slice`.
+/// Since the set holds one `bool` per value in its span, this is also the upper bound,
+/// in bytes, of the internal vector the conversion can allocate (1 GiB).
+pub const DEFAULT_MAX_SPAN: usize = 1 << 30;
+
+pub(crate) fn span_summary(min: Option<usize>, max: Option<usize>) -> String {
+    match (min, max) {
+        (Some(min), Some(max)) => format!("{}..={}", min, max),
+        _ => String::from("none"),
+    }
+}
+
+// Writes `value` as an unsigned LEB128 varint: 7 bits per byte, lowest bits first,
+// the highest bit of each byte set if more bytes follow.
+fn write_varint(w: &mut impl Write, mut value: usize) -> io::Result<()> {
+    let mut buf = [0u8; 10];
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    w.write_all(&buf[..len])
+}
+
+fn read_varint(r: &mut impl Read) -> io::Result<usize> {
+    let mut value = 0usize;
+    let mut shift = 0u32;
+    loop {
+        let mut byte = [0u8; 1];
+        r.read_exact(&mut byte)?;
+        let bits = (byte[0] & 0x7f) as usize;
+        if shift >= usize::BITS || (bits << shift) >> shift != bits {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "varint does not fit in usize",
+            ));
+        }
+        value |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Controls how the set sizes its internal vector when it has to allocate it for the first time,
+/// and when it has to reallocate it because a value does not fit in the current capacity.
+/// The default is `Fixed(INITIAL_WORKING_CAPACITY)`. With the default policy, [`push_all`]
+/// sizes the first allocation like [`from_slice`]: a single value gets exactly one slot.
+///
+/// The policy belongs to one set. It is kept by clones and by operations which modify the set
+/// in place, like `push`, `push_all`, `grow_to_include`, or `|=`. Operations which build a new
+/// set, like `+`, `-`, `shift`, or `dilate`, do not pass it on: the result has the default
+/// policy, or the policy of a cloned operand.
+///
+/// [`push_all`]: struct.USet.html#method.push_all
+/// [`from_slice`]: struct.USet.html#method.from_slice
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthPolicy {
+    /// The first allocation holds only the first value, and every reallocation makes
+    /// the vector exactly as big as needed.
+    Exact,
+    /// The first allocation holds [`INITIAL_WORKING_CAPACITY`] values, and every reallocation
+    /// at least doubles the capacity.
+    ///
+    /// [`INITIAL_WORKING_CAPACITY`]: constant.INITIAL_WORKING_CAPACITY.html
+    Double,
+    /// The first allocation holds the given number of values (at least one), and every
+    /// reallocation makes the vector exactly as big as needed.
+    Fixed(usize),
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> Self {
+        GrowthPolicy::Fixed(INITIAL_WORKING_CAPACITY)
+    }
+}
+
+impl GrowthPolicy {
+    fn initial_capacity(self) -> usize {
+        match self {
+            GrowthPolicy::Exact => 1,
+            GrowthPolicy::Double => INITIAL_WORKING_CAPACITY,
+            GrowthPolicy::Fixed(n) => cmp::max(n, 1),
+        }
+    }
+
+    fn grow(self, capacity: usize, needed: usize) -> usize {
+        match self {
+            GrowthPolicy::Double => cmp::max(needed, capacity.saturating_mul(2)),
+            _ => needed,
+        }
+    }
+}
+
 lazy_static! {
+    /// A shared empty set, for places where a `&USet` is needed but there is no set to borrow.
+    /// To get an owned empty set, use [`USet::new`] instead of cloning this one.
+    ///
+    /// [`USet::new`]: struct.USet.html#method.new
     pub static ref EMPTY_SET: USet = USet::with_capacity(0);
 }
 
 impl USet {
     /// Constructs a new, empty `USet`.
     ///
-    /// The set will not allocate until elements are pushed onto it.
+    /// The set will not allocate until elements are pushed onto it. Constructing it is cheap:
+    /// unlike cloning [`EMPTY_SET`], it does not touch any shared state.
     ///
     /// # Examples
     ///
@@ -102,8 +301,10 @@ impl USet {
     ///
     /// let set: USet = USet::new();
     /// ```
+    ///
+    /// [`EMPTY_SET`]: struct.EMPTY_SET.html
     pub fn new() -> Self {
-        EMPTY_SET.clone()
+        USet::default()
     }
 
     /// Constructs a new, empty `USet` with the specified capacity.
@@ -138,9 +339,163 @@ impl USet {
             offset: 0,
             min: 0,
             max: 0,
+            policy: GrowthPolicy::default(),
+            realloc_hook: ReallocHook::default(),
+        }
+    }
+
+    /// Returns the set with the given growth policy, which will be used for every subsequent
+    /// allocation of the internal vector. Values already in the set are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::new().with_growth_policy(GrowthPolicy::Exact);
+    /// set.push(3);
+    /// assert_eq!(1, set.capacity());
+    /// set.push(5);
+    /// assert_eq!(3, set.capacity());
+    /// ```
+    pub fn with_growth_policy(mut self, policy: GrowthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets a callback invoked with the old and the new capacity whenever the internal vector
+    /// is replaced or resized: by [`push`], [`push_all`], `|=`, and by the methods managing
+    /// the capacity, like [`grow_to_include`], [`shrink_to`] or [`compact`]. The callback is
+    /// kept when `|=` replaces the set's contents. Replaces the previous callback, if any.
+    /// Available only with the `realloc-hook` feature.
+    ///
+    /// [`push`]: #method.push
+    /// [`push_all`]: #method.push_all
+    /// [`grow_to_include`]: #method.grow_to_include
+    /// [`shrink_to`]: #method.shrink_to
+    /// [`compact`]: #method.compact
+    #[cfg(feature = "realloc-hook")]
+    pub fn on_realloc(&mut self, f: impl FnMut(usize, usize) + Send + Sync + 'static) {
+        self.realloc_hook.set(f);
+    }
+
+    /// Returns the growth policy of the set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::new();
+    /// assert_eq!(GrowthPolicy::Fixed(INITIAL_WORKING_CAPACITY), set.growth_policy());
+    /// ```
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.policy
+    }
+
+    /// Constructs a new, empty `USet` able to hold all values from `min` to `max` (inclusive)
+    /// without reallocating.
+    ///
+    /// Contrary to [`with_capacity`], the reserved window does not have to start at 0, which
+    /// saves memory when the values are known to be large.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max < min`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::with_range_capacity(100, 109);
+    /// assert_eq!(set.capacity(), 10);
+    ///
+    /// // These are all done without reallocating...
+    /// for i in (100..110).rev() {
+    ///     set.push(i);
+    /// }
+    /// assert_eq!(set.capacity(), 10);
+    ///
+    /// // ...but this may make the vector reallocate
+    /// set.push(99);
+    /// ```
+    ///
+    /// [`with_capacity`]: #method.with_capacity
+    pub fn with_range_capacity(min: usize, max: usize) -> Self {
+        assert!(min <= max, "min must not be greater than max");
+        USet {
+            vec: vec![false; max - min + 1],
+            len: 0,
+            offset: min,
+            min: 0,
+            max: 0,
+            policy: GrowthPolicy::default(),
+            realloc_hook: ReallocHook::default(),
+        }
+    }
+
+    /// Checks the invariants of the set's internal state. Does nothing in release builds.
+    pub(crate) fn check_invariants(&self) {
+        debug_assert_eq!(
+            self.len,
+            self.vec.iter().filter(|&&b| b).count(),
+            "len must be equal to the number of values"
+        );
+        if !self.is_empty() {
+            debug_assert!(
+                self.offset <= self.min,
+                "offset must not be greater than min"
+            );
+            debug_assert!(self.min <= self.max, "min must not be greater than max");
+            debug_assert!(
+                self.max - self.offset < self.capacity(),
+                "max must be within the capacity"
+            );
+            debug_assert!(
+                self.vec[self.min - self.offset],
+                "min must belong to the set"
+            );
+            debug_assert!(
+                self.vec[self.max - self.offset],
+                "max must belong to the set"
+            );
+            debug_assert_eq!(
+                self.len,
+                self.vec[self.min - self.offset..=self.max - self.offset]
+                    .iter()
+                    .filter(|&&b| b)
+                    .count(),
+                "all values must be between min and max"
+            );
         }
     }
 
+    /// Returns the number of slots needed to hold identifiers from `min` to `max`, inclusive.
+    /// Just as in [`from_slice`], the result is never smaller than [`INITIAL_WORKING_CAPACITY`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max < min`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// assert_eq!(10, USet::capacity_for(100, 109));
+    /// assert_eq!(INITIAL_WORKING_CAPACITY, USet::capacity_for(100, 102));
+    /// assert_eq!(USet::capacity_for(100, 109), USet::from_slice(&[100, 104, 109]).capacity());
+    /// assert_eq!(USet::capacity_for(100, 102), USet::from_slice(&[100, 102]).capacity());
+    /// ```
+    ///
+    /// [`from_slice`]: #method.from_slice
+    /// [`INITIAL_WORKING_CAPACITY`]: constant.INITIAL_WORKING_CAPACITY.html
+    pub fn capacity_for(min: usize, max: usize) -> usize {
+        assert!(min <= max, "min must not be greater than max");
+        cmp::max(INITIAL_WORKING_CAPACITY, max - min + 1)
+    }
+
     /// Returns the number of elements in the set, also referred to as its 'length'.
     ///
     /// # Examples
@@ -155,6 +510,26 @@ impl USet {
         self.len
     }
 
+    /// Returns `true` if the stored length of the set is equal to the number of values actually
+    /// in the internal vector. It should always be the case: this is a sanity check for tests,
+    /// which scans the whole vector but does not allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// assert!(USet::from_slice(&[1, 2, 2, 3]).verify_len());
+    /// ```
+    pub fn verify_len(&self) -> bool {
+        self.vec.iter().filter(|&&b| b).count() == self.len
+    }
+
+    #[cfg(test)]
+    pub(crate) fn corrupt_len(&mut self, len: usize) {
+        self.len = len;
+    }
+
     /// Returns `true` if the set contains no elements.
     ///
     /// # Examples
@@ -186,6 +561,47 @@ impl USet {
         self.vec.len()
     }
 
+    /// Returns the value corresponding to the first slot of the internal vector.
+    /// This is an implementation detail, useful mostly for debugging and for interop with
+    /// bitmaps: slot `n` of the internal vector corresponds to `offset() + n`.
+    /// For a non-empty set, `offset() <= min()` always holds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[100, 102]);
+    /// assert_eq!(100, set.offset());
+    /// ```
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the number of slots in the set's internal vector which lie outside of the span
+    /// between the smallest and the largest identifier, i.e. `capacity() - (max - min + 1)`.
+    /// Returns 0 for an empty set. Can be used to decide whether to call [`shrink_to_fit`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 3]);
+    /// assert_eq!(5, set.wasted_capacity());
+    /// set.shrink_to_fit();
+    /// assert_eq!(0, set.wasted_capacity());
+    /// ```
+    ///
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    pub fn wasted_capacity(&self) -> usize {
+        if self.is_empty() {
+            0
+        } else {
+            self.capacity() - (self.max - self.min + 1)
+        }
+    }
+
     /// Shrinks the set to the minimal size able to hold given values.
     ///
     /// # Examples
@@ -202,6 +618,7 @@ impl USet {
     /// ```
     pub fn shrink_to_fit(&mut self) {
         // TODO: Possible performance optimization with Vec::shrink_to_fit and other in-place operations when possible
+        let old_capacity = self.capacity();
         if !self.is_empty() && (!self.vec[0] || !self.vec[self.vec.len() - 1]) {
             let mut vec = vec![false; self.max - self.min + 1];
             for id in self.min..=self.max {
@@ -209,9 +626,118 @@ impl USet {
             }
             self.vec = vec;
             self.offset = self.min;
+            self.realloc_hook.call(old_capacity, self.capacity());
         } else if self.is_empty() && self.capacity() > 0 {
             self.vec = Vec::with_capacity(0);
+            self.realloc_hook.call(old_capacity, 0);
+        }
+        self.check_invariants();
+    }
+
+    /// Rebases the set so that the smallest identifier is at the beginning of the internal vector,
+    /// and shrinks the vector to the span between the smallest and the largest identifier.
+    /// The result is the same as calling [`rebase`] and then [`shrink_to_fit`], but the elements
+    /// are moved in place and there is at most one reallocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::with_capacity(20);
+    /// set.push(5);
+    /// set.push(8);
+    /// assert_eq!(20, set.capacity());
+    /// set.compact();
+    /// assert_eq!(4, set.capacity());
+    /// assert_eq!(set, USet::from_slice(&[5, 8]));
+    /// ```
+    ///
+    /// [`rebase`]: #method.rebase
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    pub fn compact(&mut self) {
+        let old_capacity = self.capacity();
+        if self.is_empty() {
+            if self.capacity() > 0 {
+                self.vec = Vec::with_capacity(0);
+            }
+        } else {
+            self.vec.truncate(self.max - self.offset + 1);
+            self.vec.drain(..self.min - self.offset);
+            self.vec.shrink_to_fit();
+            self.offset = self.min;
+        }
+        if self.capacity() != old_capacity {
+            self.realloc_hook.call(old_capacity, self.capacity());
+        }
+        self.check_invariants();
+    }
+
+    /// Shrinks the capacity of the set with a lower bound.
+    /// The capacity will remain at least as large as both `min_capacity` and the size needed
+    /// to hold the values (`max - min + 1`). If the current capacity is already less or equal
+    /// to that, this method does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 50]);
+    /// set.remove(1);
+    /// set.shrink_to(10);
+    /// assert_eq!(10, set.capacity());
+    /// set.shrink_to(0);
+    /// assert_eq!(1, set.capacity());
+    /// assert_eq!(set, USet::from_slice(&[50]));
+    /// ```
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let span = if self.is_empty() {
+            0
+        } else {
+            self.max - self.min + 1
+        };
+        let capacity = cmp::max(min_capacity, span);
+        if capacity < self.capacity() {
+            let old_capacity = self.capacity();
+            let mut vec = vec![false; capacity];
+            if !self.is_empty() {
+                for id in self.min..=self.max {
+                    vec[id - self.min] = self.contains(id);
+                }
+                self.offset = self.min;
+            }
+            self.vec = vec;
+            self.realloc_hook.call(old_capacity, capacity);
+        }
+        self.check_invariants();
+    }
+
+    /// Moves the values in the set's internal vector so that the smallest value is at `vec[0]`
+    /// (i.e. `offset == min`). Unused slots before the minimum are moved to the end of the vector,
+    /// so the capacity does not change and no reallocation is performed. After that, the set can
+    /// hold values up to `min + capacity - 1` without reallocation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[0, 9]);
+    /// assert_eq!(10, set.capacity());
+    /// set.remove(0);
+    /// set.rebase();
+    /// assert_eq!(10, set.capacity());
+    /// set.push(18); // no reallocation needed
+    /// assert_eq!(10, set.capacity());
+    /// assert_eq!(set, USet::from_slice(&[9, 18]));
+    /// ```
+    pub fn rebase(&mut self) {
+        if !self.is_empty() && self.offset < self.min {
+            self.vec.rotate_left(self.min - self.offset);
+            self.offset = self.min;
         }
+        self.check_invariants();
     }
 
     /// Shortens the set, keeping the first `len` elements and dropping the rest.
@@ -288,6 +814,7 @@ impl USet {
             self.max = 0;
             self.len = 0;
         }
+        self.check_invariants();
     }
 
     /// Works like [`truncate`], but returns the removed elements in the form of a new set.
@@ -355,6 +882,7 @@ impl USet {
                 });
             self.max = new_max + self.offset;
             self.len = len;
+            self.check_invariants();
             new_set.shrink_to_fit(); // TODO integrate with populating the vector
             new_set
         } else if !self.is_empty() && len == 0 {
@@ -368,34 +896,148 @@ impl USet {
             self.min = 0;
             self.max = 0;
             self.len = 0;
+            self.check_invariants();
             new_set
         } else {
-            EMPTY_SET.clone()
+            USet::new()
         }
     }
 
-    /// Clears the set, removing all values.
-    ///
-    /// Note that this method has no effect on the allocated capacity of the set.
-    /// If you want to shrink the set's capacity, call [`shrink_to_fit`] afterwards.
+    /// Removes all values within the given range from the set and returns them as a new set.
+    /// This method does not shrink the set's capacity.
     ///
     /// # Examples
     ///
     /// ```
     /// use self::uset::core::uset::*;
     ///
-    /// let mut set = USet::from_slice(&[1, 2, 3]);
+    /// let mut set = USet::from_slice(&[1, 2, 4, 5, 7, 8]);
+    /// let drained = set.drain_range(2..6);
+    /// assert_eq!(set, USet::from_slice(&[1, 7, 8]));
+    /// assert_eq!(drained, USet::from_slice(&[2, 4, 5]));
+    /// ```
+    pub fn drain_range(&mut self, range: Range<usize>) -> Self {
+        let from = cmp::max(range.start, self.min);
+        let to = cmp::min(range.end, self.max.saturating_add(1));
+        let (first, last) = if self.is_empty() {
+            (None, None)
+        } else {
+            (
+                (from..to).find(|&id| self.contains(id)),
+                (from..to).rev().find(|&id| self.contains(id)),
+            )
+        };
+        if let (Some(first), Some(last)) = (first, last) {
+            let mut drained = USet::with_range_capacity(first, last);
+            for id in first..=last {
+                if self.vec[id - self.offset] {
+                    self.vec[id - self.offset] = false;
+                    drained.push(id);
+                }
+            }
+            self.len -= drained.len();
+            if self.is_empty() {
+                self.offset = 0;
+                self.min = 0;
+                self.max = 0;
+            } else if first == self.min {
+                self.min = (last + 1..=self.max)
+                    .find(|&id| self.vec[id - self.offset])
+                    .unwrap();
+            } else if last == self.max {
+                self.max = (self.min..first)
+                    .rev()
+                    .find(|&id| self.vec[id - self.offset])
+                    .unwrap();
+            }
+            self.check_invariants();
+            drained
+        } else {
+            USet::new()
+        }
+    }
+
+    /// Returns an iterator which removes the values from the set in the ascending order,
+    /// one by one, as they are produced. When fully consumed, the set is left empty.
+    /// If the iterator is dropped early, only the values produced so far are removed.
     ///
-    /// set.clear();
+    /// # Examples
     ///
-    /// assert!(set.is_empty());
     /// ```
+    /// use self::uset::core::uset::*;
     ///
-    /// [`shrink_to_fit`]: #method.shrink_to_fit
+    /// let mut set = USet::from_slice(&[1, 3, 8, 10]);
+    /// let drained: Vec<usize> = set.drain_iter().take(2).collect();
+    /// assert_eq!(vec![1, 3], drained);
+    /// assert_eq!(set, USet::from_slice(&[8, 10]));
+    /// ```
+    pub fn drain_iter(&mut self) -> impl Iterator<Item = usize> + '_ {
+        std::iter::from_fn(move || {
+            let id = self.min()?;
+            self.remove(id);
+            Some(id)
+        })
+    }
+
+    /// Clears the set, removing all values.
+    ///
+    /// Note that this method has no effect on the allocated capacity of the set.
+    /// If you want to shrink the set's capacity, call [`shrink_to_fit`] afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 2, 3]);
+    ///
+    /// set.clear();
+    ///
+    /// assert!(set.is_empty());
+    /// ```
+    ///
+    /// [`shrink_to_fit`]: #method.shrink_to_fit
     pub fn clear(&mut self) {
         self.truncate(0)
     }
 
+    /// Empties the set and prepares it for values from `min` to `max` (inclusive), as if it
+    /// was created with [`with_range_capacity`], but reusing the internal vector: it is only
+    /// enlarged if it is too small for the new window, and never shrunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max < min`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 2, 3, 10]);
+    /// set.reset_to(100, 104);
+    /// assert!(set.is_empty());
+    /// assert_eq!(10, set.capacity());
+    /// set.push(104);
+    /// assert_eq!(10, set.capacity());
+    /// ```
+    ///
+    /// [`with_range_capacity`]: #method.with_range_capacity
+    pub fn reset_to(&mut self, min: usize, max: usize) {
+        assert!(min <= max, "min must not be greater than max");
+        self.vec.iter_mut().for_each(|value| *value = false);
+        let old_capacity = self.capacity();
+        if old_capacity < max - min + 1 {
+            self.vec.resize(max - min + 1, false);
+            self.realloc_hook.call(old_capacity, self.capacity());
+        }
+        self.len = 0;
+        self.offset = min;
+        self.min = 0;
+        self.max = 0;
+        self.check_invariants();
+    }
+
     /// Changes the set's capacity, so that it can hold new elements up to the `new_capacity + offset - 1`
     /// value without reallocation. Note that `new_capacity + offset - 1` is now the largest **value**
     /// the set can hold without the reallocation, not the total number of values that can be held.
@@ -416,8 +1058,53 @@ impl USet {
     /// ```
     pub fn enlarge_capacity_to(&mut self, new_capacity: usize) {
         if new_capacity > self.capacity() {
+            let old_capacity = self.capacity();
+            self.vec.resize(new_capacity, false);
+            self.realloc_hook.call(old_capacity, new_capacity);
+        }
+        self.check_invariants();
+    }
+
+    /// Makes sure the id falls within the allocated window, without adding it to the set.
+    /// If the id is lower than the current offset, the window is extended downwards.
+    /// A subsequent [`push`] of the id will not trigger reallocation.
+    ///
+    /// [`push`]: #method.push
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[5, 8]);
+    /// set.grow_to_include(2);
+    /// assert_eq!(11, set.capacity());
+    /// assert!(!set.contains(2));
+    /// assert_eq!(2, set.len());
+    /// set.push(2); // no reallocation needed
+    /// assert_eq!(11, set.capacity());
+    /// ```
+    pub fn grow_to_include(&mut self, id: usize) {
+        let old_capacity = self.capacity();
+        if self.capacity() == 0 {
+            self.vec = vec![false; self.policy.initial_capacity()];
+            self.offset = id;
+            self.realloc_hook.call(old_capacity, self.capacity());
+        } else if self.is_empty() && (id < self.offset || id >= self.offset + self.capacity()) {
+            self.offset = id;
+        } else if id < self.offset {
+            let extra = self.offset - id;
+            let new_capacity = self.policy.grow(self.capacity(), self.capacity() + extra);
+            self.vec.splice(0..0, std::iter::repeat(false).take(extra));
+            self.vec.resize(new_capacity, false);
+            self.offset = id;
+            self.realloc_hook.call(old_capacity, new_capacity);
+        } else if id >= self.offset + self.capacity() {
+            let new_capacity = self.policy.grow(self.capacity(), id + 1 - self.offset);
             self.vec.resize(new_capacity, false);
+            self.realloc_hook.call(old_capacity, new_capacity);
         }
+        self.check_invariants();
     }
 
     /// Adds the id to the set, and reallocates if needed.
@@ -435,13 +1122,20 @@ impl USet {
     pub fn push(&mut self, id: usize) {
         match id {
             _ if self.capacity() == 0 => {
-                self.vec = vec![false; INITIAL_WORKING_CAPACITY];
+                self.vec = vec![false; self.policy.initial_capacity()];
+                self.realloc_hook.call(0, self.capacity());
                 self.vec[0] = true;
                 self.min = id;
                 self.len += 1;
                 self.max = id;
                 self.offset = id;
             }
+            _ if self.is_empty() && id >= self.offset && id < self.offset + self.capacity() => {
+                self.vec[id - self.offset] = true;
+                self.min = id;
+                self.len = 1;
+                self.max = id;
+            }
             _ if self.is_empty() => {
                 self.vec[0] = true;
                 self.min = id;
@@ -450,18 +1144,23 @@ impl USet {
                 self.offset = id;
             }
             _ if id < self.offset => {
-                let mut vec = vec![false; self.max - id + 1];
+                let mut vec = vec![false; self.policy.grow(self.capacity(), self.max - id + 1)];
                 vec[0] = true;
                 for i in self.min..=self.max {
                     vec[i - id] = self.contains(i);
                 }
+                let old_capacity = self.capacity();
                 self.vec = vec;
+                self.realloc_hook.call(old_capacity, self.capacity());
                 self.len += 1;
                 self.min = id;
                 self.offset = id;
             }
             _ if id >= self.offset + self.capacity() => {
-                self.vec.resize(id + 1 - self.offset, false);
+                let old_capacity = self.capacity();
+                let new_capacity = self.policy.grow(old_capacity, id + 1 - self.offset);
+                self.vec.resize(new_capacity, false);
+                self.realloc_hook.call(old_capacity, new_capacity);
                 self.vec[id - self.offset] = true;
                 self.len += 1;
                 self.max = id;
@@ -477,6 +1176,7 @@ impl USet {
             }
             _ => {}
         }
+        self.check_invariants();
     }
 
     /// Removes the id from the set. Does nothing if the id is not in the set.
@@ -521,10 +1221,33 @@ impl USet {
             }
             _ => {}
         }
+        self.check_invariants();
     }
 
-    /// Removes all the identifiers belonging to the `other` set from `self`. Ignores identifiers
-    /// from `other` which do not belong in `self`.
+    /// Removes the id from the set and returns it, or returns `None` if the id is not in the set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 2, 3]);
+    /// assert_eq!(set.take(2), Some(2));
+    /// assert_eq!(set.take(2), None);
+    /// assert_eq!(set, USet::from_slice(&[1, 3]));
+    /// ```
+    pub fn take(&mut self, id: usize) -> Option<usize> {
+        if self.contains(id) {
+            self.remove(id);
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    /// Removes all the given identifiers from `self`. The identifiers can come from another set,
+    /// or from any other collection or iterator of `usize`s. Ignores identifiers which do not
+    /// belong in `self`.
     /// Equivalent to calling [`remove`] multiple times. Does not reallocate.
     ///
     /// # Examples
@@ -532,15 +1255,98 @@ impl USet {
     /// ```
     /// use self::uset::core::uset::*;
     ///
-    /// let mut set1 = USet::from_slice(&[1, 2, 3, 4]);
+    /// let mut set1 = USet::from_slice(&[1, 2, 3, 4, 6, 7]);
     /// let set2 = USet::from_slice(&[2, 3, 5]);
     /// set1.remove_all(&set2);
-    /// assert_eq!(set1, USet::from_slice(&[1, 4]));
+    /// assert_eq!(set1, USet::from_slice(&[1, 4, 6, 7]));
+    /// set1.remove_all(vec![4, 7, 9]);
+    /// assert_eq!(set1, USet::from_slice(&[1, 6]));
     /// ```
     ///
     /// [`remove`]: #method.remove
-    pub fn remove_all(&mut self, other: &Self) {
-        other.iter().for_each(|id| self.remove(id));
+    pub fn remove_all(&mut self, ids: impl IntoIterator<Item = usize>) {
+        ids.into_iter().for_each(|id| self.remove(id));
+    }
+
+    /// Removes from `self` all values which belong to `other`, in place, keeping the internal
+    /// vector. Unlike [`remove_all`], it does not update `min` and `max` after every removed
+    /// value: only the values of `other` which fall within `self`'s span are visited, and
+    /// the bookkeeping is recomputed once at the end. If nothing is left, the set is reset
+    /// just as after [`clear`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 2, 3, 4, 6, 7]);
+    /// set.difference_into(&USet::from_slice(&[1, 2, 5, 7, 100]));
+    /// assert_eq!(set, USet::from_slice(&[3, 4, 6]));
+    /// assert_eq!(Some(3), set.min());
+    /// assert_eq!(Some(6), set.max());
+    /// ```
+    ///
+    /// [`remove_all`]: #method.remove_all
+    /// [`clear`]: #method.clear
+    pub fn difference_into(&mut self, other: &USet) {
+        if self.is_empty() || other.is_empty() {
+            return;
+        }
+        let offset = self.offset;
+        // `range` is exclusive, so `usize::MAX` has to be checked separately.
+        let last = Some(usize::MAX).filter(|&id| id == self.max && other.contains(id));
+        for id in other
+            .range(self.min..self.max.saturating_add(1))
+            .chain(last)
+        {
+            if self.vec[id - offset] {
+                self.vec[id - offset] = false;
+                self.len -= 1;
+            }
+        }
+        if self.len == 0 {
+            self.offset = 0;
+            self.min = 0;
+            self.max = 0;
+        } else {
+            self.min = (self.min..=self.max)
+                .find(|&i| self.vec[i - offset])
+                .unwrap_or(self.min);
+            self.max = (self.min..=self.max)
+                .rev()
+                .find(|&i| self.vec[i - offset])
+                .unwrap_or(self.min);
+        }
+        self.check_invariants();
+    }
+
+    /// Intersects the set with an external bitmap. Bit `n` of the bitmap (counting from the least
+    /// significant bit of `mask[0]`) corresponds to the identifier `offset + n`. Values of the set
+    /// with the corresponding bit unset, or outside of the bitmap, are removed.
+    /// Since the set is not bit-packed, the mask is applied value by value, so there is no
+    /// requirement for `offset` to be aligned with the set's internal offset.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 10, 11, 12, 80]);
+    /// set.mask_and(10, &[0b101]);
+    /// assert_eq!(set, USet::from_slice(&[10, 12]));
+    /// ```
+    pub fn mask_and(&mut self, offset: usize, mask: &[u64]) {
+        let to_remove: Vec<usize> = self
+            .iter()
+            .filter(|&id| {
+                id < offset || {
+                    let bit = id - offset;
+                    mask.get(bit / 64)
+                        .map_or(true, |word| (word >> (bit % 64)) & 1 == 0)
+                }
+            })
+            .collect();
+        self.remove_all(to_remove);
     }
 
     /// Returns true if `self` is a subset of `other`.
@@ -566,11 +1372,203 @@ impl USet {
     /// assert!(set4.is_subset_of(&set4));
     /// ```
     pub fn is_subset_of(&self, other: &USet) -> bool {
-        if self.len > other.len {
+        if self.is_empty() {
+            true
+        } else if self.len > other.len || self.min < other.min || self.max > other.max {
             false
         } else {
-            self.iter().find(|id| !other.contains(*id)).is_none()
+            // both windows cover self.min..=self.max, so they can be compared slot by slot
+            let mine = &self.vec[self.min - self.offset..=self.max - self.offset];
+            let theirs = &other.vec[self.min - other.offset..=self.max - other.offset];
+            mine.iter().zip(theirs.iter()).all(|(&a, &b)| !a || b)
+        }
+    }
+
+    /// Returns true if `other` is a subset of `self`. This is the mirror of [`is_subset_of`]:
+    /// `a.contains_subset(&b) == b.is_subset_of(&a)`.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set1 = USet::from_slice(&[1, 2, 3]);
+    /// let set2 = USet::from_slice(&[2, 3]);
+    /// assert!(set1.contains_subset(&set2));
+    /// assert!(!set2.contains_subset(&set1));
+    /// assert!(set1.contains_subset(&USet::new()));
+    /// ```
+    ///
+    /// [`is_subset_of`]: #method.is_subset_of
+    pub fn contains_subset(&self, other: &USet) -> bool {
+        other.is_subset_of(self)
+    }
+
+    /// Returns the number of values which belong to both sets.
+    /// This is the same as `(&a * &b).len()`, but the common part is not created.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set1 = USet::from_slice(&[1, 2, 3, 8]);
+    /// let set2 = USet::from_slice(&[2, 3, 5]);
+    /// assert_eq!(2, set1.overlap_len(&set2));
+    /// assert_eq!((&set1 * &set2).len(), set1.overlap_len(&set2));
+    /// assert_eq!(0, set1.overlap_len(&USet::new()));
+    /// ```
+    pub fn overlap_len(&self, other: &USet) -> usize {
+        if self.is_empty() || other.is_empty() {
+            0
+        } else {
+            (cmp::max(self.min, other.min)..=cmp::min(self.max, other.max))
+                .filter(|&id| self.contains(id) && other.contains(id))
+                .count()
+        }
+    }
+
+    /// Returns the Jaccard similarity of two sets, i.e. the size of their common part divided
+    /// by the size of their union. Two empty sets are considered identical, with the similarity `1.0`.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set1 = USet::from_slice(&[1, 2, 3, 8]);
+    /// let set2 = USet::from_slice(&[2, 3, 5]);
+    /// assert_eq!(0.4, set1.jaccard_similarity(&set2));
+    /// assert_eq!(1.0, USet::new().jaccard_similarity(&USet::new()));
+    /// ```
+    pub fn jaccard_similarity(&self, other: &USet) -> f64 {
+        if self.is_empty() && other.is_empty() {
+            1.0
+        } else {
+            let overlap = self.overlap_len(other);
+            overlap as f64 / (self.len + other.len - overlap) as f64
+        }
+    }
+
+    /// Returns the Jaccard distance of two sets, equal to `1.0 - jaccard_similarity`.
+    /// The distance between two empty sets is `0.0`.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set1 = USet::from_slice(&[1, 2, 3, 8]);
+    /// let set2 = USet::from_slice(&[2, 3, 5]);
+    /// assert_eq!(0.6, set1.jaccard_distance(&set2));
+    /// assert_eq!(0.0, USet::new().jaccard_distance(&USet::new()));
+    /// ```
+    pub fn jaccard_distance(&self, other: &USet) -> f64 {
+        1.0 - self.jaccard_similarity(other)
+    }
+
+    /// Returns the number of values which belong to this set but not to the other one.
+    /// This is the same as `(&a - &b).len()`, but the difference is not created.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set1 = USet::from_slice(&[1, 2, 3, 8]);
+    /// let set2 = USet::from_slice(&[2, 3, 5]);
+    /// assert_eq!(2, set1.difference_len(&set2));
+    /// assert_eq!((&set1 - &set2).len(), set1.difference_len(&set2));
+    /// ```
+    pub fn difference_len(&self, other: &USet) -> usize {
+        self.len - self.overlap_len(other)
+    }
+
+    /// Returns the number of values which belong to exactly one of the two sets.
+    /// This is the same as `(&a ^ &b).len()`, but the symmetric difference is not created.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set1 = USet::from_slice(&[1, 2, 3, 8]);
+    /// let set2 = USet::from_slice(&[2, 3, 5]);
+    /// assert_eq!(3, set1.symmetric_difference_len(&set2));
+    /// assert_eq!((&set1 ^ &set2).len(), set1.symmetric_difference_len(&set2));
+    /// ```
+    pub fn symmetric_difference_len(&self, other: &USet) -> usize {
+        self.len + other.len - 2 * self.overlap_len(other)
+    }
+
+    /// Returns the symmetric difference of the set and the range: the values of the set outside
+    /// the range, together with the values in the range which do not belong to the set.
+    /// Works like `&set ^ &USet::from_range(range)`, but the range is not turned into a set.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 3, 8]);
+    /// assert_eq!(USet::from_slice(&[1, 2, 4, 8]), set.symmetric_difference_range(2..5));
+    /// assert_eq!(set, set.symmetric_difference_range(5..5));
+    /// ```
+    pub fn symmetric_difference_range(&self, range: Range<usize>) -> USet {
+        if range.is_empty() {
+            return self.clone();
+        }
+        let (lo, hi) = if self.is_empty() {
+            (range.start, range.end - 1)
+        } else {
+            (
+                cmp::min(self.min, range.start),
+                cmp::max(self.max, range.end - 1),
+            )
+        };
+        let mut vec = vec![false; hi - lo + 1];
+        let mut len = 0usize;
+        let mut min = None;
+        let mut max = lo;
+        for id in lo..=hi {
+            if self.contains(id) != range.contains(&id) {
+                vec[id - lo] = true;
+                len += 1;
+                min = min.or(Some(id));
+                max = id;
+            }
         }
+        match min {
+            None => USet::new(),
+            Some(min) => USet {
+                vec,
+                len,
+                offset: lo,
+                min,
+                max,
+                policy: GrowthPolicy::default(),
+                realloc_hook: ReallocHook::default(),
+            },
+        }
+    }
+
+    /// Compares the values of two sets lexicographically, i.e. as sorted sequences.
+    /// The first pair of different values decides the ordering. If one set is a prefix of
+    /// the other, the shorter set is smaller, so the empty set is smaller than any other set,
+    /// and two empty sets are equal.
+    /// `USet` does not implement `Ord`, but this method can be used with `sort_by`.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    /// use std::cmp::Ordering;
+    ///
+    /// let set1 = USet::from_slice(&[1, 2, 5]);
+    /// let set2 = USet::from_slice(&[1, 3]);
+    /// assert_eq!(Ordering::Less, set1.cmp_elements(&set2));
+    /// assert_eq!(Ordering::Greater, set1.cmp_elements(&USet::from_slice(&[1, 2])));
+    /// assert_eq!(Ordering::Less, USet::new().cmp_elements(&set1));
+    /// assert_eq!(Ordering::Equal, USet::new().cmp_elements(&USet::new()));
+    ///
+    /// let mut sets = vec![set2.clone(), USet::new(), set1.clone()];
+    /// sets.sort_by(|a, b| a.cmp_elements(b));
+    /// assert_eq!(vec![USet::new(), set1, set2], sets);
+    /// ```
+    pub fn cmp_elements(&self, other: &USet) -> cmp::Ordering {
+        self.iter().cmp(other.iter())
     }
 
     /// Removes and returns the element at position `index` within the set.
@@ -593,7 +1591,52 @@ impl USet {
         d
     }
 
+    /// Removes and returns the largest value in the set, or `None` if the set is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 2, 3]);
+    /// assert_eq!(set.pop_back(), Some(3));
+    /// assert_eq!(set, USet::from_slice(&[1, 2]));
+    /// assert_eq!(USet::new().pop_back(), None);
+    /// ```
+    pub fn pop_back(&mut self) -> Option<usize> {
+        let max = self.max();
+        if let Some(id) = max {
+            self.remove(id);
+        }
+        max
+    }
+
+    /// Removes and returns a value from the set chosen at random with the given random number
+    /// generator, or `None` if the set is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 2, 3]);
+    /// let id = set.pop_random(&mut rand::thread_rng()).unwrap();
+    /// assert!(id >= 1 && id <= 3);
+    /// assert!(!set.contains(id));
+    /// assert_eq!(2, set.len());
+    /// ```
+    pub fn pop_random(&mut self, rng: &mut impl Rng) -> Option<usize> {
+        if self.is_empty() {
+            None
+        } else {
+            let index = rng.gen_range(0, self.len);
+            self.pop(index)
+        }
+    }
+
     /// Returns an iterator over the set.
+    /// Values are always yielded in the ascending order. The order depends only on the contents
+    /// of the set, not on its capacity or on how it was built, so two equal sets iterate identically.
     ///
     /// # Examples
     ///
@@ -608,7 +1651,7 @@ impl USet {
     /// assert_eq!(iterator.next(), Some(4));
     /// assert_eq!(iterator.next(), None);
     /// ```
-    pub fn iter(&self) -> USetIter {
+    pub fn iter(&self) -> USetIter<'_> {
         USetIter {
             handle: self,
             index: 0,
@@ -616,6 +1659,385 @@ impl USet {
         }
     }
 
+    /// Collects the values of the set into a new vector, in the ascending order.
+    /// Unlike `Vec::from`, this method does not consume the set, and unlike [`iter`],
+    /// the result does not borrow it, so it can be used e.g. in a loop which modifies the set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::from_slice(&[1, 2, 4]);
+    /// for value in set.to_vec() {
+    ///     set.push(value * 10);
+    /// }
+    /// assert_eq!(set.to_vec(), vec![1, 2, 4, 10, 20, 40]);
+    /// ```
+    ///
+    /// [`iter`]: #method.iter
+    pub fn to_vec(&self) -> Vec<usize> {
+        self.iter().collect()
+    }
+
+    /// Returns an iterator over the values of the set which fall within the given range.
+    /// Only the part of the set's internal vector overlapping with the range is scanned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 3, 8, 10]);
+    /// let mut iterator = set.range(2..9);
+    ///
+    /// assert_eq!(iterator.next(), Some(3));
+    /// assert_eq!(iterator.next(), Some(8));
+    /// assert_eq!(iterator.next(), None);
+    /// ```
+    pub fn range(&self, range: Range<usize>) -> USetIter<'_> {
+        if self.is_empty() || range.start > self.max || range.end <= self.min {
+            USetIter {
+                handle: self,
+                index: self.vec.len(),
+                rindex: 0,
+            }
+        } else {
+            let from = cmp::max(range.start, self.min) - self.offset;
+            let to = cmp::min(range.end - 1, self.max) - self.offset + 1;
+            USetIter {
+                handle: self,
+                index: from,
+                rindex: self.vec.len() - to,
+            }
+        }
+    }
+
+    /// Treats the values of the set as indices into `slice` and returns an iterator over
+    /// pairs of a value and the element of `slice` it points to. Values which are out of bounds
+    /// of `slice` are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[0, 2, 5]);
+    /// let letters = ["a", "b", "c"];
+    /// let mut iterator = set.index_into(&letters);
+    ///
+    /// assert_eq!(iterator.next(), Some((0, &"a")));
+    /// assert_eq!(iterator.next(), Some((2, &"c")));
+    /// assert_eq!(iterator.next(), None);
+    /// ```
+    pub fn index_into<'a, T>(&'a self, slice: &'a [T]) -> impl Iterator<Item = (usize, &'a T)> {
+        self.range(0..slice.len()).map(move |id| (id, &slice[id]))
+    }
+
+    /// Returns the number of values within the given range which belong to the set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 3, 8, 10]);
+    /// assert_eq!(2, set.count_ones(0..5));
+    /// assert_eq!(4, set.count_ones(0..100));
+    /// ```
+    pub fn count_ones(&self, range: Range<usize>) -> usize {
+        self.range(range).count()
+    }
+
+    /// Returns the number of values within the given range which do not belong to the set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 3, 8, 10]);
+    /// assert_eq!(3, set.count_zeros(0..5));
+    /// assert_eq!(96, set.count_zeros(0..100));
+    /// ```
+    pub fn count_zeros(&self, range: Range<usize>) -> usize {
+        range.end.saturating_sub(range.start) - self.count_ones(range)
+    }
+
+    /// Returns an iterator over maximal ranges of consecutive values in the set, in ascending order.
+    /// The ranges are computed lazily, as the iterator advances.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 2, 3, 7, 8]);
+    /// let mut iterator = set.ranges();
+    ///
+    /// assert_eq!(iterator.next(), Some(1..=3));
+    /// assert_eq!(iterator.next(), Some(7..=8));
+    /// assert_eq!(iterator.next(), None);
+    /// ```
+    pub fn ranges(&self) -> USetRanges<'_> {
+        USetRanges {
+            iter: self.iter().peekable(),
+        }
+    }
+
+    /// Returns an iterator over the points where membership in the set changes, in ascending
+    /// order: `(id, true)` where a run of consecutive values starts, and `(id, false)` one past
+    /// the last value of a run. Computed lazily from [`ranges`]. A run ending at `usize::MAX`
+    /// has no closing edge, as there is no value past it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 2, 4]);
+    /// let edges: Vec<(usize, bool)> = set.edges().collect();
+    /// assert_eq!(edges, vec![(1, true), (3, false), (4, true), (5, false)]);
+    ///
+    /// let set = USet::from_slice(&[usize::MAX]);
+    /// assert_eq!(vec![(usize::MAX, true)], set.edges().collect::<Vec<_>>());
+    /// ```
+    ///
+    /// [`ranges`]: #method.ranges
+    pub fn edges(&self) -> impl Iterator<Item = (usize, bool)> + '_ {
+        self.ranges().flat_map(|run| {
+            let end = run.end().checked_add(1).map(|id| (id, false));
+            std::iter::once((*run.start(), true)).chain(end)
+        })
+    }
+
+    /// Returns a cursor over the set. The cursor works like the iterator returned by [`iter`],
+    /// but it also allows to peek at the next value without consuming it, and to reposition
+    /// the cursor at any value with `seek`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 3, 8, 10]);
+    /// let mut cursor = set.cursor();
+    ///
+    /// assert_eq!(cursor.peek(), Some(1));
+    /// assert_eq!(cursor.next(), Some(1));
+    /// cursor.seek(4);
+    /// assert_eq!(cursor.peek(), Some(8));
+    /// assert_eq!(cursor.next(), Some(8));
+    /// assert_eq!(cursor.next(), Some(10));
+    /// assert_eq!(cursor.peek(), None);
+    /// ```
+    ///
+    /// [`iter`]: #method.iter
+    pub fn cursor(&self) -> USetCursor<'_> {
+        USetCursor { iter: self.iter() }
+    }
+
+    /// Returns the number of maximal ranges of consecutive values in the set.
+    /// It is the same as `ranges().count()`, but computed in one scan of the internal vector.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// assert_eq!(0, USet::new().count_runs());
+    /// assert_eq!(1, USet::from_slice(&[1, 2, 3]).count_runs());
+    /// assert_eq!(3, USet::from_slice(&[1, 3, 5]).count_runs());
+    /// assert_eq!(2, USet::from_slice(&[1, 2, 3, 7, 8]).count_runs());
+    /// ```
+    pub fn count_runs(&self) -> usize {
+        if self.is_empty() {
+            0
+        } else {
+            let mut previous = false;
+            let mut runs = 0usize;
+            self.vec[self.min - self.offset..=self.max - self.offset]
+                .iter()
+                .for_each(|&value| {
+                    if value && !previous {
+                        runs += 1;
+                    }
+                    previous = value;
+                });
+            runs
+        }
+    }
+
+    /// Returns the fraction of values between `min` and `max` (inclusive) which are in the set,
+    /// or 0.0 if the set is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// assert_eq!(0.0, USet::new().density());
+    /// assert_eq!(1.0, USet::from_slice(&[1, 2, 3]).density());
+    /// assert_eq!(0.4, USet::from_slice(&[1, 2, 9, 10]).density());
+    /// ```
+    pub fn density(&self) -> f64 {
+        if self.is_empty() {
+            0.0
+        } else {
+            self.len as f64 / (self.max - self.min + 1) as f64
+        }
+    }
+
+    /// Returns a short description of the set, with its length, span, number of runs
+    /// and density, but without listing the values. Intended for logs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 2, 9, 10]);
+    /// assert_eq!("USet{len=4, span=1..=10, runs=2, density=0.40}", set.summary());
+    /// assert_eq!("USet{len=0, span=none, runs=0, density=0.00}", USet::new().summary());
+    /// ```
+    pub fn summary(&self) -> String {
+        format!(
+            "USet{{len={}, span={}, runs={}, density={:.2}}}",
+            self.len,
+            span_summary(self.min(), self.max()),
+            self.count_runs(),
+            self.density()
+        )
+    }
+
+    /// Writes the set to `w` in a compact form: the number of runs of consecutive values,
+    /// followed by the gap before each run and the run's length, all encoded as LEB128 varints.
+    /// The encoding is streamed run by run, without building a buffer for the whole set,
+    /// so sets made of long runs take only a few bytes. Read it back with [`read_from`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 2, 3, 10, 11]);
+    /// let mut bytes = Vec::new();
+    /// set.write_to(&mut bytes).unwrap();
+    /// assert_eq!(vec![2, 1, 3, 6, 2], bytes);
+    /// ```
+    ///
+    /// [`read_from`]: #method.read_from
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        write_varint(w, self.count_runs())?;
+        let mut next = 0usize;
+        for run in self.ranges() {
+            write_varint(w, run.start() - next)?;
+            write_varint(w, run.end() - run.start() + 1)?;
+            // no run follows one ending at usize::MAX
+            next = run.end().wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    /// Reads a set written with [`write_to`] from `r`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from `r` fails, if the input ends too early, if it
+    /// describes values which do not fit in `usize`, or if the span of the values is bigger
+    /// than [`DEFAULT_MAX_SPAN`]. In the last two cases the error kind is `InvalidData`.
+    /// Use [`read_from_with_limit`] to set a different limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    /// use std::io::Cursor;
+    ///
+    /// let mut cursor = Cursor::new(vec![2, 1, 3, 6, 2]);
+    /// let set = USet::read_from(&mut cursor).unwrap();
+    /// assert_eq!(USet::from_slice(&[1, 2, 3, 10, 11]), set);
+    /// ```
+    ///
+    /// [`write_to`]: #method.write_to
+    /// [`DEFAULT_MAX_SPAN`]: constant.DEFAULT_MAX_SPAN.html
+    /// [`read_from_with_limit`]: #method.read_from_with_limit
+    pub fn read_from(r: &mut impl Read) -> io::Result<USet> {
+        USet::read_from_with_limit(r, DEFAULT_MAX_SPAN)
+    }
+
+    /// Reads a set written with [`write_to`] from `r`, like [`read_from`], but fails with
+    /// an `InvalidData` error if the span of the values (`max - min + 1`) is bigger than `limit`.
+    /// The input is rejected before the internal vector is allocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    /// use std::io::{Cursor, ErrorKind};
+    ///
+    /// let bytes = vec![2, 1, 3, 6, 2];
+    /// let set = USet::read_from_with_limit(&mut Cursor::new(&bytes), 11).unwrap();
+    /// assert_eq!(USet::from_slice(&[1, 2, 3, 10, 11]), set);
+    ///
+    /// let err = USet::read_from_with_limit(&mut Cursor::new(&bytes), 10).unwrap_err();
+    /// assert_eq!(ErrorKind::InvalidData, err.kind());
+    /// ```
+    ///
+    /// [`write_to`]: #method.write_to
+    /// [`read_from`]: #method.read_from
+    pub fn read_from_with_limit(r: &mut impl Read, limit: usize) -> io::Result<USet> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let count = read_varint(r)?;
+        let mut runs = Vec::with_capacity(cmp::min(count, 1024));
+        // `None` once a run has ended at usize::MAX, as no value can follow it
+        let mut next = Some(0usize);
+        for _ in 0..count {
+            let gap = read_varint(r)?;
+            let start = next
+                .and_then(|next| next.checked_add(gap))
+                .ok_or_else(|| invalid("value out of range"))?;
+            let len = read_varint(r)?;
+            if len == 0 {
+                return Err(invalid("value out of range"));
+            }
+            let end = start
+                .checked_add(len - 1)
+                .ok_or_else(|| invalid("value out of range"))?;
+            next = end.checked_add(1);
+            let min = runs
+                .first()
+                .map_or(start, |first: &RangeInclusive<usize>| *first.start());
+            if end - min >= limit {
+                return Err(invalid("span too large"));
+            }
+            runs.push(start..=end);
+        }
+        match (runs.first(), runs.last()) {
+            (Some(first), Some(last)) => {
+                let (min, max) = (*first.start(), *last.end());
+                let mut vec = vec![false; max - min + 1];
+                let mut len = 0usize;
+                runs.iter().for_each(|run| {
+                    vec[run.start() - min..=run.end() - min].fill(true);
+                    len += run.end() - run.start() + 1;
+                });
+                let set = USet {
+                    vec,
+                    len,
+                    offset: min,
+                    min,
+                    max,
+                    policy: GrowthPolicy::default(),
+                    realloc_hook: ReallocHook::default(),
+                };
+                set.check_invariants();
+                Ok(set)
+            }
+            _ => Ok(USet::new()),
+        }
+    }
+
     /// Returns `true` if the set contains the given id.
     ///
     /// # Examples
@@ -629,7 +2051,76 @@ impl USet {
     /// assert_eq!(set.contains(2), false);
     /// ```
     pub fn contains(&self, id: usize) -> bool {
-        id >= self.min && id <= self.max && self.vec[id - self.offset]
+        !self.is_empty() && id >= self.min && id <= self.max && self.vec[id - self.offset]
+    }
+
+    /// Returns a vector of the same length as `ids`, where the element at the position `i`
+    /// is `true` if the set contains `ids[i]`, just as [`contains`] would return.
+    ///
+    /// Instead of comparing each id with the smallest and the largest element of the set,
+    /// this method only checks if the id falls within the internal vector: every slot of
+    /// the vector outside of `min..=max` is empty anyway.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 3]);
+    /// assert_eq!(set.membership_mask(&[1, 2, 3, 4]), vec![true, false, true, false]);
+    /// ```
+    ///
+    /// [`contains`]: #method.contains
+    pub fn membership_mask(&self, ids: &[usize]) -> Vec<bool> {
+        ids.iter()
+            .map(|&id| {
+                id.checked_sub(self.offset)
+                    .and_then(|index| self.vec.get(index))
+                    == Some(&true)
+            })
+            .collect()
+    }
+
+    /// Returns `true` if the set contains at least one of the given ids.
+    /// Returns `false` for an empty slice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 3, 8]);
+    /// assert!(set.contains_any(&[2, 3, 4]));
+    /// assert!(!set.contains_any(&[0, 2, 100]));
+    /// assert!(!set.contains_any(&[]));
+    /// ```
+    pub fn contains_any(&self, ids: &[usize]) -> bool {
+        !self.is_empty() && ids.iter().any(|&id| self.contains(id))
+    }
+
+    /// Returns the set of those of the given ids which are in this set.
+    /// Duplicates in `ids` are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[1, 3, 8]);
+    /// assert_eq!(USet::from_slice(&[3, 8]), set.filter_present(&[2, 3, 3, 8, 9]));
+    /// assert!(set.filter_present(&[0, 2, 100]).is_empty());
+    /// ```
+    pub fn filter_present(&self, ids: &[usize]) -> USet {
+        if self.is_empty() {
+            USet::new()
+        } else {
+            let present: Vec<usize> = ids
+                .iter()
+                .copied()
+                .filter(|&id| self.contains(id))
+                .collect();
+            USet::from_slice(&present)
+        }
     }
 
     /// The set allows to access its values by index.
@@ -659,6 +2150,32 @@ impl USet {
         }
     }
 
+    /// Builds a [`RankSelectIndex`] of the set, which gives the `k`-th value of the set in O(1)
+    /// and the position of a value in O(log n), instead of O(n) of [`at_index`].
+    /// Worth it when there are many positional lookups on a set which does not change.
+    /// The index is a snapshot: it becomes invalid after any mutation of the set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[2, 10, 40]);
+    /// let index = set.build_index();
+    /// assert_eq!(Some(10), index.select(1));
+    /// assert_eq!(None, index.select(3));
+    /// assert_eq!(2, index.rank(40));
+    /// assert_eq!(1, index.rank(5));
+    /// ```
+    ///
+    /// [`RankSelectIndex`]: struct.RankSelectIndex.html
+    /// [`at_index`]: #method.at_index
+    pub fn build_index(&self) -> RankSelectIndex {
+        let mut values = Vec::with_capacity(self.len);
+        values.extend(self.iter());
+        RankSelectIndex { values }
+    }
+
     /// Returns the smallest element in the set or None if the set is empty.
     ///
     /// ```
@@ -709,15 +2226,86 @@ impl USet {
         }
     }
 
+    /// Returns the range from the smallest to the largest element in the set,
+    /// or None if the set is empty.
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// assert_eq!(USet::new().span(), None);
+    ///
+    /// let set = USet::from_slice(&[3, 5, 9]);
+    /// assert_eq!(set.span(), Some(3..=9));
+    /// ```
+    pub fn span(&self) -> Option<RangeInclusive<usize>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.min..=self.max)
+        }
+    }
+
+    /// Returns the smallest element in the set, assuming the set is not empty.
+    /// Useful in tight loops where the set is known to be non-empty and the `Option`
+    /// returned by [`min`] would only add a branch.
+    ///
+    /// # Panics
+    ///
+    /// The set must not be empty. This is checked only in debug builds: in release builds,
+    /// the result for an empty set is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[2, 5, 7]);
+    /// assert_eq!(2, set.min_unchecked());
+    /// ```
+    ///
+    /// [`min`]: #method.min
+    pub fn min_unchecked(&self) -> usize {
+        debug_assert!(!self.is_empty(), "min_unchecked called on an empty set");
+        self.min
+    }
+
+    /// Returns the largest element in the set, assuming the set is not empty.
+    /// The counterpart of [`min_unchecked`].
+    ///
+    /// # Panics
+    ///
+    /// The set must not be empty. This is checked only in debug builds: in release builds,
+    /// the result for an empty set is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[2, 5, 7]);
+    /// assert_eq!(7, set.max_unchecked());
+    /// ```
+    ///
+    /// [`min_unchecked`]: #method.min_unchecked
+    pub fn max_unchecked(&self) -> usize {
+        debug_assert!(!self.is_empty(), "max_unchecked called on an empty set");
+        self.max
+    }
+
     fn make_from_slice(slice: &[usize]) -> (usize, usize, usize, Vec<bool>) {
         match slice.iter().minmax() {
             MinMaxResult::NoElements => (0, 0, 0, Vec::<bool>::new()),
             MinMaxResult::OneElement(&min) => (min, min, 1, vec![true]),
             MinMaxResult::MinMax(&min, &max) => {
-                let len = slice.len();
-                let capacity = cmp::max(INITIAL_WORKING_CAPACITY, max + 1 - min);
+                let capacity = USet::capacity_for(min, max);
                 let mut vec = vec![false; capacity];
-                slice.iter().for_each(|&id| vec[id - min] = true);
+                let mut len = 0usize;
+                slice.iter().for_each(|&id| {
+                    if !vec[id - min] {
+                        vec[id - min] = true;
+                        len += 1;
+                    }
+                });
                 (min, max, len, vec)
             }
         }
@@ -740,7 +2328,7 @@ impl USet {
     /// ```
     pub fn from_slice(slice: &[usize]) -> Self {
         if slice.is_empty() {
-            EMPTY_SET.clone()
+            USet::new()
         } else {
             let (min, max, len, new_vec) = USet::make_from_slice(slice);
             USet {
@@ -749,7 +2337,91 @@ impl USet {
                 offset: min,
                 min,
                 max,
+                policy: GrowthPolicy::default(),
+                realloc_hook: ReallocHook::default(),
+            }
+        }
+    }
+
+    /// Creates a set from the slice, like [`from_slice`], but fails with
+    /// [`UsetError::TooLarge`] if the span of the values is bigger than [`DEFAULT_MAX_SPAN`].
+    /// Use [`try_from_slice_with_limit`] to set a different limit.
+    ///
+    /// `USet` cannot implement `TryFrom<&[usize]>` for this, because it already implements
+    /// `From<&[usize]>`, which comes with an infallible `TryFrom`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::error::UsetError;
+    /// use self::uset::core::uset::*;
+    ///
+    /// assert_eq!(Ok(USet::from_slice(&[0, 10])), USet::try_from_slice(&[0, 10]));
+    /// assert_eq!(Err(UsetError::TooLarge), USet::try_from_slice(&[0, 10_000_000_000]));
+    /// ```
+    ///
+    /// [`from_slice`]: #method.from_slice
+    /// [`UsetError::TooLarge`]: ../error/enum.UsetError.html#variant.TooLarge
+    /// [`DEFAULT_MAX_SPAN`]: constant.DEFAULT_MAX_SPAN.html
+    /// [`try_from_slice_with_limit`]: #method.try_from_slice_with_limit
+    pub fn try_from_slice(slice: &[usize]) -> Result<Self, UsetError> {
+        USet::try_from_slice_with_limit(slice, DEFAULT_MAX_SPAN)
+    }
+
+    /// Creates a set from the slice, like [`from_slice`], but fails with
+    /// [`UsetError::TooLarge`] if the span of the values, `max - min + 1`, is bigger than `limit`.
+    /// This guards against allocating a huge internal vector because of one stray value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::error::UsetError;
+    /// use self::uset::core::uset::*;
+    ///
+    /// assert_eq!(Ok(USet::from_slice(&[1, 5])), USet::try_from_slice_with_limit(&[1, 5], 5));
+    /// assert_eq!(Err(UsetError::TooLarge), USet::try_from_slice_with_limit(&[1, 6], 5));
+    /// ```
+    ///
+    /// [`from_slice`]: #method.from_slice
+    /// [`UsetError::TooLarge`]: ../error/enum.UsetError.html#variant.TooLarge
+    pub fn try_from_slice_with_limit(slice: &[usize], limit: usize) -> Result<Self, UsetError> {
+        match slice.iter().minmax() {
+            MinMaxResult::MinMax(&min, &max) if max - min >= limit => Err(UsetError::TooLarge),
+            MinMaxResult::OneElement(_) if limit == 0 => Err(UsetError::TooLarge),
+            _ => Ok(USet::from_slice(slice)),
+        }
+    }
+
+    /// Creates a set from an unsorted vector which may contain duplicates. The vector is sorted
+    /// and deduplicated in place, and then the set is built in one pass, allocating once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_unsorted(vec![3, 1, 1, 2]);
+    /// assert_eq!(set, USet::from_slice(&[1, 2, 3]));
+    /// assert_eq!(3, set.len());
+    /// ```
+    pub fn from_unsorted(mut v: Vec<usize>) -> Self {
+        v.sort_unstable();
+        v.dedup();
+        match (v.first(), v.last()) {
+            (Some(&min), Some(&max)) => {
+                let mut vec = vec![false; USet::capacity_for(min, max)];
+                v.iter().for_each(|&id| vec[id - min] = true);
+                USet {
+                    vec,
+                    len: v.len(),
+                    offset: min,
+                    min,
+                    max,
+                    policy: GrowthPolicy::default(),
+                    realloc_hook: ReallocHook::default(),
+                }
             }
+            _ => USet::new(),
         }
     }
 
@@ -767,14 +2439,14 @@ impl USet {
     /// assert!(set.contains(3));
     /// assert!(set.contains(4));
     /// assert!(set.contains(5));
+    /// assert_eq!(Some(5), set.max());
     /// ```
     pub fn from_range(r: Range<usize>) -> Self {
-        if r.len() == 0 {
-            // is_empty is unstable for ranges, don't let clippy tell you otherwise
-            EMPTY_SET.clone()
+        if r.is_empty() {
+            USet::new()
         } else {
             let offset = r.start;
-            let max = r.end;
+            let max = r.end - 1;
             let len = r.len();
             let capacity = cmp::max(INITIAL_WORKING_CAPACITY, r.len());
             let mut vec = vec![false; capacity];
@@ -785,6 +2457,54 @@ impl USet {
                 offset,
                 min: offset,
                 max,
+                policy: GrowthPolicy::default(),
+                realloc_hook: ReallocHook::default(),
+            }
+        }
+    }
+
+    /// Creates a set of all values in the range for which `f` returns `true`.
+    /// The internal vector is allocated once, with the size of the range, and filled in one pass.
+    /// Works like filtering the range and passing the result to [`from_slice`], but without
+    /// collecting the values first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let evens = USet::from_range_where(0..10, |id| id % 2 == 0);
+    /// assert_eq!(evens, USet::from_slice(&[0, 2, 4, 6, 8]));
+    /// assert!(USet::from_range_where(0..10, |id| id > 100).is_empty());
+    /// ```
+    ///
+    /// [`from_slice`]: #method.from_slice
+    pub fn from_range_where(r: Range<usize>, f: impl Fn(usize) -> bool) -> Self {
+        if r.is_empty() {
+            return USet::new();
+        }
+        let offset = r.start;
+        let mut vec = vec![false; r.len()];
+        let mut len = 0usize;
+        let mut min = usize::MAX;
+        let mut max = 0usize;
+        r.filter(|&id| f(id)).for_each(|id| {
+            vec[id - offset] = true;
+            len += 1;
+            min = cmp::min(min, id);
+            max = id;
+        });
+        if len == 0 {
+            USet::new()
+        } else {
+            USet {
+                vec,
+                len,
+                offset,
+                min,
+                max,
+                policy: GrowthPolicy::default(),
+                realloc_hook: ReallocHook::default(),
             }
         }
     }
@@ -807,7 +2527,7 @@ impl USet {
     /// ```
     pub fn from_fields(vec: Vec<bool>, offset: usize) -> Self {
         if vec.is_empty() {
-            EMPTY_SET.clone()
+            USet::new()
         } else {
             let len = vec.iter().filter(|&b| *b).count();
             let min = vec
@@ -829,6 +2549,8 @@ impl USet {
                 offset,
                 min,
                 max,
+                policy: GrowthPolicy::default(),
+                realloc_hook: ReallocHook::default(),
             }
         }
     }
@@ -859,47 +2581,351 @@ impl USet {
     /// assert!(set.contains(5));
     /// ```
     pub fn push_all(&mut self, slice: &[usize]) {
-        if !slice.is_empty() {
-            if self.is_empty() {
-                let (min, max, len, new_vec) = USet::make_from_slice(slice);
+        self.push_all_reporting(slice);
+    }
+
+    /// Adds all elements in the slice to the set, just like [`push_all`], and returns `true`
+    /// if the internal vector had to be reallocated, or `false` if all the elements fit within
+    /// the current capacity.
+    ///
+    /// # Examples
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let mut set = USet::with_capacity(10);
+    /// assert!(!set.push_all_reporting(&[0]));
+    /// assert!(!set.push_all_reporting(&[3, 9]));
+    /// assert!(set.push_all_reporting(&[20]));
+    /// assert_eq!(set, USet::from_slice(&[0, 3, 9, 20]));
+    /// ```
+    ///
+    /// [`push_all`]: #method.push_all
+    pub fn push_all_reporting(&mut self, slice: &[usize]) -> bool {
+        let (min, max) = match slice.iter().minmax() {
+            MinMaxResult::NoElements => return false,
+            MinMaxResult::OneElement(&min) => (min, min),
+            MinMaxResult::MinMax(&min, &max) => (min, max),
+        };
+        let fits = |set: &USet| min >= set.offset && max - set.offset < set.capacity();
+        // an empty set can move its window anywhere, as long as the slice fits in the capacity
+        if self.is_empty() && !fits(self) && max - min < self.capacity() {
+            self.offset = min;
+        }
+
+        let reallocated = if fits(self) {
+            let was_empty = self.is_empty();
+            slice.iter().for_each(|&id| {
+                if !self.vec[id - self.offset] {
+                    self.vec[id - self.offset] = true;
+                    self.len += 1;
+                }
+            });
+            if was_empty {
                 self.min = min;
                 self.max = max;
-                self.offset = min;
-                self.len = len;
-                self.vec = new_vec;
             } else {
-                let (min, max) = match slice.iter().minmax() {
-                    MinMaxResult::NoElements => (0, 0), // should not happen
-                    MinMaxResult::OneElement(&min) => (min, min),
-                    MinMaxResult::MinMax(&min, &max) => (min, max),
-                };
+                self.min = cmp::min(self.min, min);
+                self.max = cmp::max(self.max, max);
+            }
+            false
+        } else if self.is_empty() {
+            let capacity = if self.policy == GrowthPolicy::default() {
+                // sized like `from_slice`, so a single value gets exactly one slot
+                if min == max {
+                    1
+                } else {
+                    USet::capacity_for(min, max)
+                }
+            } else {
+                cmp::max(self.policy.initial_capacity(), max - min + 1)
+            };
+            let mut new_vec = vec![false; capacity];
+            slice.iter().for_each(|&id| {
+                if !new_vec[id - min] {
+                    new_vec[id - min] = true;
+                    self.len += 1;
+                }
+            });
+            let old_capacity = self.capacity();
+            self.min = min;
+            self.max = max;
+            self.offset = min;
+            self.vec = new_vec;
+            self.realloc_hook.call(old_capacity, self.capacity());
+            true
+        } else {
+            let new_min = cmp::min(self.min, min);
+            let new_max = cmp::max(self.max, max);
+            let old_capacity = self.capacity();
+            let new_capacity = self.policy.grow(old_capacity, new_max - new_min + 1);
+            let mut new_vec = vec![false; new_capacity];
+            self.iter().for_each(|id| new_vec[id - new_min] = true);
+            slice.iter().for_each(|&id| {
+                if !new_vec[id - new_min] {
+                    new_vec[id - new_min] = true;
+                    self.len += 1;
+                }
+            });
+            self.min = new_min;
+            self.offset = new_min;
+            self.max = new_max;
+            self.vec = new_vec;
+            self.realloc_hook.call(old_capacity, new_capacity);
+            true
+        };
+        self.check_invariants();
+        reallocated
+    }
 
-                if min >= self.min && max <= self.max {
-                    slice.iter().for_each(|&id| {
-                        if !self.vec[id - self.offset] {
-                            self.vec[id - self.offset] = true;
-                            self.len += 1;
-                        }
-                    })
+    /// Returns a new set containing all values within `radius` of any value of this set
+    /// (a morphological dilation). The result is clamped to `0..=usize::MAX`, so dilating
+    /// values close to 0 only extends them down to 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[5]);
+    /// assert_eq!(set.dilate(1), USet::from_slice(&[4, 5, 6]));
+    ///
+    /// let set = USet::from_slice(&[1, 8]);
+    /// assert_eq!(set.dilate(2), USet::from_slice(&[0, 1, 2, 3, 6, 7, 8, 9, 10]));
+    /// ```
+    pub fn dilate(&self, radius: usize) -> Self {
+        if self.is_empty() || radius == 0 {
+            self.clone()
+        } else {
+            let min = self.min.saturating_sub(radius);
+            let max = self.max.saturating_add(radius);
+            let mut vec = vec![false; max - min + 1];
+            let mut len = 0usize;
+            self.ranges().for_each(|range| {
+                let from = range.start().saturating_sub(radius);
+                let to = range.end().saturating_add(radius);
+                (from..=to).for_each(|id| {
+                    if !vec[id - min] {
+                        vec[id - min] = true;
+                        len += 1;
+                    }
+                });
+            });
+            USet {
+                vec,
+                len,
+                offset: min,
+                min,
+                max,
+                policy: GrowthPolicy::default(),
+                realloc_hook: ReallocHook::default(),
+            }
+        }
+    }
+
+    /// Returns a new set containing only those values of this set for which all values
+    /// within `radius` also belong to the set (a morphological erosion). Since there are no
+    /// values below 0, values smaller than `radius` are always removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[4, 5, 6]);
+    /// assert_eq!(set.erode(1), USet::from_slice(&[5]));
+    ///
+    /// let set = USet::from_slice(&[0, 1, 2, 3, 6, 7, 8, 9, 10]);
+    /// assert_eq!(set.erode(2), USet::from_slice(&[8]));
+    /// ```
+    pub fn erode(&self, radius: usize) -> Self {
+        if self.is_empty() || radius == 0 {
+            self.clone()
+        } else {
+            let vec: Vec<usize> = self
+                .ranges()
+                .flat_map(|range| {
+                    range.start().saturating_add(radius)..=range.end().saturating_sub(radius)
+                })
+                .collect();
+            USet::from_slice(&vec)
+        }
+    }
+
+    /// Returns a new set with the values of this set reflected around `center`, i.e. every
+    /// value `n` is replaced with `2 * center - n`. Values for which the reflection would be
+    /// below 0 (or above `usize::MAX`) are rejected: they are skipped and not present in the
+    /// new set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    ///
+    /// let set = USet::from_slice(&[3, 4]);
+    /// assert_eq!(set.mirror(5), USet::from_slice(&[6, 7]));
+    ///
+    /// let set = USet::from_slice(&[1, 2, 9]);
+    /// assert_eq!(set.mirror(3), USet::from_slice(&[4, 5]));
+    /// ```
+    pub fn mirror(&self, center: usize) -> Self {
+        let vec: Vec<usize> = self
+            .iter()
+            .rev()
+            .filter_map(|id| {
+                if id <= center {
+                    center.checked_add(center - id)
                 } else {
-                    let new_min = cmp::min(self.min, min);
-                    let new_max = cmp::max(self.max, max);
-                    let mut new_vec = vec![false; new_max - new_min + 1];
-                    self.iter()
-                        .skip(self.min - self.offset)
-                        .take(self.max - self.min + 1)
-                        .for_each(|id| new_vec[id - new_min] = true);
-                    slice.iter().for_each(|&id| {
-                        if !new_vec[id - new_min] {
-                            new_vec[id - new_min] = true;
-                            self.len += 1;
-                        }
-                    });
-                    self.min = new_min;
-                    self.offset = new_min;
-                    self.max = new_max;
-                    self.vec = new_vec;
+                    center.checked_sub(id - center)
+                }
+            })
+            .collect();
+        USet::from_slice(&vec)
+    }
+
+    /// Returns a new set with all values of this set moved by `delta`, which can be negative.
+    /// Fails with [`IdOverflow`] if any of the values would go below 0 or above `usize::MAX`.
+    /// This is the same as [`shift_with`] with [`IdArith::Checked`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    /// use self::uset::core::id_arith::*;
+    ///
+    /// let set = USet::from_slice(&[3, 4, 8]);
+    /// assert_eq!(Ok(USet::from_slice(&[5, 6, 10])), set.shift(2));
+    /// assert_eq!(Ok(USet::from_slice(&[0, 1, 5])), set.shift(-3));
+    /// assert_eq!(Err(IdOverflow { id: 3 }), set.shift(-4));
+    /// ```
+    ///
+    /// [`IdOverflow`]: ../id_arith/struct.IdOverflow.html
+    /// [`IdArith::Checked`]: ../id_arith/enum.IdArith.html#variant.Checked
+    /// [`shift_with`]: #method.shift_with
+    pub fn shift(&self, delta: isize) -> Result<Self, IdOverflow> {
+        self.shift_with(delta, IdArith::Checked)
+    }
+
+    /// Returns a new set with all values of this set moved by `delta`, which can be negative.
+    /// Values which would go below 0 or above `usize::MAX` are handled according to `mode`.
+    /// With [`IdArith::Saturating`], more than one value may end up at the same boundary,
+    /// so the new set may be smaller than this one. With [`IdArith::Wrapping`], the shift fails
+    /// with [`IdOverflow`] if only some of the values wrap around, because the new set would
+    /// span almost the whole range of identifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    /// use self::uset::core::id_arith::*;
+    ///
+    /// let set = USet::from_slice(&[1, 2, 5]);
+    /// assert_eq!(Ok(USet::from_slice(&[0, 3])), set.shift_with(-2, IdArith::Saturating));
+    /// assert_eq!(Err(IdOverflow { id: 1 }), set.shift_with(-2, IdArith::Checked));
+    /// assert_eq!(Err(IdOverflow { id: 1 }), set.shift_with(-3, IdArith::Wrapping));
+    /// let wrapped = USet::from_slice(&[usize::MAX - 4, usize::MAX - 3, usize::MAX]);
+    /// assert_eq!(Ok(wrapped), set.shift_with(-6, IdArith::Wrapping));
+    /// ```
+    ///
+    /// [`IdArith::Saturating`]: ../id_arith/enum.IdArith.html#variant.Saturating
+    /// [`IdArith::Wrapping`]: ../id_arith/enum.IdArith.html#variant.Wrapping
+    /// [`IdOverflow`]: ../id_arith/struct.IdOverflow.html
+    pub fn shift_with(&self, delta: isize, mode: IdArith) -> Result<Self, IdOverflow> {
+        self.move_ids(mode, |mode, id| mode.shift(id, delta))
+    }
+
+    /// Returns a new set with the values of this set reflected around `center`, i.e. every
+    /// value `n` is replaced with `2 * center - n`. Contrary to [`mirror`], which skips values
+    /// with reflections out of range, here they are handled according to `mode`. As in
+    /// [`shift_with`], wrapping fails if only some of the reflections wrap around.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    /// use self::uset::core::id_arith::*;
+    ///
+    /// let set = USet::from_slice(&[1, 2, 9]);
+    /// assert_eq!(Ok(USet::from_slice(&[0, 4, 5])), set.mirror_with(3, IdArith::Saturating));
+    /// assert_eq!(Err(IdOverflow { id: 9 }), set.mirror_with(3, IdArith::Checked));
+    /// assert_eq!(Err(IdOverflow { id: 9 }), set.mirror_with(3, IdArith::Wrapping));
+    /// ```
+    ///
+    /// [`mirror`]: #method.mirror
+    /// [`shift_with`]: #method.shift_with
+    pub fn mirror_with(&self, center: usize, mode: IdArith) -> Result<Self, IdOverflow> {
+        self.move_ids(mode, |mode, id| {
+            if id <= center {
+                mode.add(center, center - id)
+            } else {
+                mode.sub(center, id - center)
+            }
+            .map_err(|_| IdOverflow { id })
+        })
+    }
+
+    // Maps every value with `f` under `mode`. With `IdArith::Wrapping`, if only some values
+    // wrap around, the new set would span from near 0 to near `usize::MAX`, so it fails with
+    // the first value which wrapped.
+    fn move_ids<F>(&self, mode: IdArith, f: F) -> Result<Self, IdOverflow>
+    where
+        F: Fn(IdArith, usize) -> Result<usize, IdOverflow>,
+    {
+        let mut wrapped = None;
+        let mut kept = false;
+        let vec = self
+            .iter()
+            .map(|id| {
+                if mode == IdArith::Wrapping {
+                    if f(IdArith::Checked, id).is_ok() {
+                        kept = true;
+                    } else if wrapped.is_none() {
+                        wrapped = Some(id);
+                    }
                 }
+                f(mode, id)
+            })
+            .collect::<Result<Vec<usize>, IdOverflow>>()?;
+        match wrapped {
+            Some(id) if kept => Err(IdOverflow { id }),
+            _ => Ok(USet::from_slice(&vec)),
+        }
+    }
+
+    /// Returns a new set with every value of this set extended to all values within `radius`,
+    /// like [`dilate`], but with values below 0 or above `usize::MAX` handled according to `mode`.
+    /// [`dilate`] itself works like [`IdArith::Saturating`]. A wrapped dilation would span almost
+    /// the whole range of identifiers, so [`IdArith::Wrapping`] fails just like
+    /// [`IdArith::Checked`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use self::uset::core::uset::*;
+    /// use self::uset::core::id_arith::*;
+    ///
+    /// let set = USet::from_slice(&[1, 8]);
+    /// assert_eq!(Ok(set.dilate(2)), set.dilate_with(2, IdArith::Saturating));
+    /// assert_eq!(Err(IdOverflow { id: 1 }), set.dilate_with(2, IdArith::Checked));
+    /// assert_eq!(Ok(set.dilate(1)), set.dilate_with(1, IdArith::Checked));
+    /// assert_eq!(Err(IdOverflow { id: 1 }), set.dilate_with(2, IdArith::Wrapping));
+    /// ```
+    ///
+    /// [`dilate`]: #method.dilate
+    /// [`IdArith::Saturating`]: ../id_arith/enum.IdArith.html#variant.Saturating
+    /// [`IdArith::Wrapping`]: ../id_arith/enum.IdArith.html#variant.Wrapping
+    /// [`IdArith::Checked`]: ../id_arith/enum.IdArith.html#variant.Checked
+    pub fn dilate_with(&self, radius: usize, mode: IdArith) -> Result<Self, IdOverflow> {
+        if self.is_empty() || radius == 0 {
+            return Ok(self.clone());
+        }
+        match mode {
+            IdArith::Saturating => Ok(self.dilate(radius)),
+            // A wrapped dilation always keeps the values around the original ones as well,
+            // so it can be represented only if nothing wraps, just like the checked one.
+            IdArith::Checked | IdArith::Wrapping => {
+                IdArith::Checked.sub(self.min, radius)?;
+                IdArith::Checked.add(self.max, radius)?;
+                Ok(self.dilate(radius))
             }
         }
     }
@@ -907,16 +2933,20 @@ impl USet {
     fn union(&self, other: &Self) -> Self {
         if self.is_empty() {
             if other.is_empty() {
-                EMPTY_SET.clone()
+                USet::new()
             } else {
                 other.clone()
             }
         } else if other.is_empty() {
             if self.is_empty() {
-                EMPTY_SET.clone()
+                USet::new()
             } else {
                 self.clone()
             }
+        } else if other.is_subset_of(self) {
+            self.clone()
+        } else if self.is_subset_of(other) {
+            other.clone()
         } else {
             let min: usize = cmp::min(self.min, other.min);
             let max: usize = cmp::max(self.max, other.max);
@@ -937,6 +2967,8 @@ impl USet {
                 offset: min,
                 min,
                 max,
+                policy: GrowthPolicy::default(),
+                realloc_hook: ReallocHook::default(),
             }
         }
     }
@@ -953,7 +2985,7 @@ impl USet {
         });
 
         if len == 0 {
-            EMPTY_SET.clone()
+            USet::new()
         } else {
             let min = vec
                 .iter()
@@ -974,13 +3006,15 @@ impl USet {
                 offset: self.offset,
                 min,
                 max,
+                policy: GrowthPolicy::default(),
+                realloc_hook: ReallocHook::default(),
             }
         }
     }
 
     fn common_part(&self, other: &USet) -> Self {
         if self.is_empty() || other.is_empty() {
-            EMPTY_SET.clone()
+            USet::new()
         } else {
             let rough_range = cmp::max(self.min, other.min)..=cmp::min(self.max, other.max);
             let mn = rough_range
@@ -1006,19 +3040,21 @@ impl USet {
                         offset: min,
                         min,
                         max,
+                        policy: GrowthPolicy::default(),
+                        realloc_hook: ReallocHook::default(),
                     }
                 } else {
-                    EMPTY_SET.clone()
+                    USet::new()
                 }
             } else {
-                EMPTY_SET.clone()
+                USet::new()
             }
         }
     }
 
     fn xor_set(&self, other: &USet) -> Self {
         if self.is_empty() && other.is_empty() {
-            EMPTY_SET.clone()
+            USet::new()
         } else if self.is_empty() {
             other.clone()
         } else if other.is_empty() {
@@ -1051,12 +3087,14 @@ impl USet {
                         offset: min,
                         min,
                         max,
+                        policy: GrowthPolicy::default(),
+                        realloc_hook: ReallocHook::default(),
                     }
                 } else {
-                    EMPTY_SET.clone()
+                    USet::new()
                 }
             } else {
-                EMPTY_SET.clone()
+                USet::new()
             }
         }
     }
@@ -1064,6 +3102,10 @@ impl USet {
 
 impl PartialEq for USet {
     fn eq(&self, other: &USet) -> bool {
+        if self.is_empty() || other.is_empty() {
+            // an empty set may keep its window anywhere, so its `min` says nothing about `offset`
+            return self.len == other.len;
+        }
         self.len == other.len
             && self.min == other.min
             && self.max == other.max
@@ -1071,13 +3113,13 @@ impl PartialEq for USet {
                 .vec
                 .iter()
                 .skip(self.min - self.offset)
-                .take(self.max + 1 - self.min)
+                .take(self.max - self.min + 1)
                 .zip(
                     other
                         .vec
                         .iter()
                         .skip(other.min - other.offset)
-                        .take(other.max + 1 - other.min),
+                        .take(other.max - other.min + 1),
                 )
                 .all(|(&a, &b)| a == b)
     }
@@ -1085,28 +3127,102 @@ impl PartialEq for USet {
 
 impl Eq for USet {}
 
-impl<'a> Add for &'a USet {
+/// Formats the set as a bitfield over the values from `min` to `max`: `1` for values which
+/// belong to the set and `0` for those which do not. The least significant (rightmost) bit
+/// stands for `min`, and the most significant (leftmost) one for `max`, so the leftmost
+/// digit is always `1`. The empty set is formatted as `0`.
+///
+/// # Examples
+///
+/// ```
+/// use self::uset::core::uset::*;
+///
+/// let set = USet::from_slice(&[0, 2, 3]);
+/// assert_eq!("1101", format!("{:b}", set));
+/// assert_eq!("0b1101", format!("{:#b}", set));
+/// assert_eq!("0", format!("{:b}", USet::new()));
+/// ```
+impl fmt::Binary for USet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bits: String = if self.is_empty() {
+            String::from("0")
+        } else {
+            (self.min..=self.max)
+                .rev()
+                .map(|id| if self.contains(id) { '1' } else { '0' })
+                .collect()
+        };
+        f.pad_integral(true, "0b", &bits)
+    }
+}
+
+impl Add for &USet {
     type Output = USet;
     fn add(self, other: &USet) -> USet {
         self.union(other)
     }
 }
 
-impl<'a> Sub for &'a USet {
+/// The union of two sets. `&a | &b` is equivalent to `&a + &b`.
+///
+/// # Examples
+///
+/// ```
+/// use self::uset::core::uset::*;
+///
+/// let set1 = USet::from_slice(&[1, 2]);
+/// let set2 = USet::from_slice(&[2, 5]);
+/// assert_eq!(&set1 | &set2, USet::from_slice(&[1, 2, 5]));
+/// assert_eq!(&set1 | &set2, &set1 + &set2);
+/// ```
+impl BitOr for &USet {
+    type Output = USet;
+    fn bitor(self, other: &USet) -> USet {
+        self.union(other)
+    }
+}
+
+/// In-place union of two sets. `a |= &b` is equivalent to `a = &a | &b`.
+///
+/// # Examples
+///
+/// ```
+/// use self::uset::core::uset::*;
+///
+/// let mut set1 = USet::from_slice(&[1, 2]);
+/// set1 |= &USet::from_slice(&[2, 5]);
+/// assert_eq!(set1, USet::from_slice(&[1, 2, 5]));
+/// ```
+impl BitOrAssign<&USet> for USet {
+    fn bitor_assign(&mut self, other: &USet) {
+        if other.is_subset_of(self) {
+            return;
+        }
+        let policy = self.policy;
+        let realloc_hook = std::mem::take(&mut self.realloc_hook);
+        let old_capacity = self.capacity();
+        *self = self.union(other);
+        self.policy = policy;
+        self.realloc_hook = realloc_hook;
+        self.realloc_hook.call(old_capacity, self.capacity());
+    }
+}
+
+impl Sub for &USet {
     type Output = USet;
     fn sub(self, other: &USet) -> USet {
         self.difference(other)
     }
 }
 
-impl<'a> Mul for &'a USet {
+impl Mul for &USet {
     type Output = USet;
     fn mul(self, other: &USet) -> USet {
         self.common_part(other)
     }
 }
 
-impl<'a> BitXor for &'a USet {
+impl BitXor for &USet {
     type Output = USet;
     fn bitxor(self, other: &USet) -> USet {
         self.xor_set(other)
@@ -1125,9 +3241,9 @@ impl From<Vec<usize>> for USet {
     }
 }
 
-impl Into<Vec<usize>> for USet {
-    fn into(self) -> Vec<usize> {
-        self.iter().collect()
+impl From<USet> for Vec<usize> {
+    fn from(set: USet) -> Self {
+        set.to_vec()
     }
 }
 
@@ -1153,16 +3269,42 @@ impl From<Range<usize>> for USet {
 }
 
 impl FromIterator<usize> for USet {
+    /// Builds the set by pushing values one by one, without collecting them first.
+    /// The internal vector grows in amortized steps both upwards and downwards, so even values
+    /// coming in descending order do not cause a reallocation on every push.
     fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
-        let vec: Vec<usize> = iter.into_iter().collect();
-        USet::from_slice(&vec)
+        let mut set = USet::new();
+        for id in iter {
+            if id < set.offset && set.capacity() > 0 {
+                let step = cmp::max(set.offset - id, set.capacity());
+                let new_offset = set.offset.saturating_sub(step);
+                set.vec
+                    .splice(0..0, std::iter::repeat(false).take(set.offset - new_offset));
+                set.offset = new_offset;
+            }
+            set.push(id);
+        }
+        set
     }
 }
 
 impl Extend<usize> for USet {
+    /// Adds all values from the iterator to the set.
+    /// If the iterator reports it will yield at least [`INITIAL_WORKING_CAPACITY`] values,
+    /// they are first collected and added with [`push_all`], so the set reallocates at most once.
+    /// Otherwise the values are pushed one by one.
+    ///
+    /// [`INITIAL_WORKING_CAPACITY`]: constant.INITIAL_WORKING_CAPACITY.html
+    /// [`push_all`]: struct.USet.html#method.push_all
     fn extend<T: IntoIterator<Item = usize>>(&mut self, iter: T) {
-        for id in iter {
-            self.push(id);
+        let iter = iter.into_iter();
+        if iter.size_hint().0 >= INITIAL_WORKING_CAPACITY {
+            let vec: Vec<usize> = iter.collect();
+            self.push_all(&vec);
+        } else {
+            for id in iter {
+                self.push(id);
+            }
         }
     }
 }
@@ -1,5 +1,14 @@
 #[cfg(test)]
+#[allow(
+    clippy::module_inception,
+    clippy::bool_comparison,
+    clippy::manual_next_back,
+    clippy::needless_borrow,
+    clippy::needless_borrows_for_generic_args,
+    clippy::useless_vec
+)]
 mod uset_tests {
+    use crate::core::umap::UMap;
     use crate::core::uset::*;
 
     use std::collections::HashSet;
@@ -315,4 +324,1252 @@ mod uset_tests {
         assert_that!(set.contains(11));
         assert_that!(set.contains(8) == false);
     }
+
+    #[test]
+    fn should_rebase() {
+        let mut s = uset![2, 3, 5, 8];
+        s.remove(2);
+        s.remove(3);
+        s.rebase();
+        assert_eq!(INITIAL_WORKING_CAPACITY, s.capacity());
+        assert_eq!(uset![5, 8], s);
+        assert_eq!(Some(5), s.min());
+        assert_eq!(Some(8), s.max());
+
+        // after rebasing, the whole capacity is available above the minimum
+        s.push(12);
+        assert_eq!(INITIAL_WORKING_CAPACITY, s.capacity());
+        assert_eq!(uset![5, 8, 12], s);
+
+        let mut empty = USet::new();
+        empty.rebase();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn should_shrink_to_min_capacity() {
+        let mut s = uset![3, 5, 30];
+        assert_eq!(28, s.capacity());
+        s.remove(30);
+        s.shrink_to(10);
+        assert_eq!(10, s.capacity());
+        assert_eq!(uset![3, 5], s);
+
+        // the floor is below the size needed to hold the values
+        s.shrink_to(1);
+        assert_eq!(3, s.capacity());
+        assert_eq!(uset![3, 5], s);
+
+        // shrinking to a capacity larger than the current one does nothing
+        s.shrink_to(20);
+        assert_eq!(3, s.capacity());
+
+        let mut empty = USet::with_capacity(10);
+        empty.shrink_to(4);
+        assert_eq!(4, empty.capacity());
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn should_push_into_reserved_range() {
+        let mut s = USet::with_range_capacity(1000, 1015);
+        assert_eq!(16, s.capacity());
+        assert!(s.is_empty());
+        for &id in &[1007, 1000, 1015, 1003] {
+            s.push(id);
+            assert_eq!(16, s.capacity());
+        }
+        assert_eq!(uset![1000, 1003, 1007, 1015], s);
+        assert_eq!(Some(1000), s.min());
+        assert_eq!(Some(1015), s.max());
+    }
+
+    #[test]
+    fn should_compare_empty_reserved_range_with_new() {
+        let reserved = USet::with_range_capacity(100, 109);
+        assert_eq!(USet::new(), reserved);
+        assert_eq!(reserved, USet::new());
+        assert_ne!(uset![100], reserved);
+        assert_ne!(reserved, uset![0]);
+    }
+
+    #[test]
+    fn should_push_all_into_reserved_range() {
+        let mut s = USet::with_range_capacity(1000, 1015);
+        s.push_all(&[1007, 1003, 1007, 1012]);
+        assert_eq!(16, s.capacity());
+        assert_eq!(1000, s.offset());
+        assert_eq!(uset![1003, 1007, 1012], s);
+        assert_eq!(Some(1003), s.min());
+        assert_eq!(Some(1012), s.max());
+
+        s.push_all(&[1000, 1015]);
+        assert_eq!(16, s.capacity());
+        assert_eq!(uset![1000, 1003, 1007, 1012, 1015], s);
+    }
+
+    #[test]
+    fn should_iterate_ranges() {
+        let s = uset![1, 2, 3, 7, 8];
+        let ranges: Vec<_> = s.ranges().collect();
+        assert_eq!(vec![1..=3, 7..=8], ranges);
+
+        let s2 = uset![0, 2, 4];
+        let ranges2: Vec<_> = s2.ranges().collect();
+        assert_eq!(vec![0..=0, 2..=2, 4..=4], ranges2);
+
+        assert_eq!(None, USet::new().ranges().next());
+
+        let top = uset![usize::MAX - 3, usize::MAX - 1, usize::MAX];
+        assert_eq!(
+            vec![usize::MAX - 3..=usize::MAX - 3, usize::MAX - 1..=usize::MAX],
+            top.ranges().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_count_runs() {
+        assert_eq!(0, USet::new().count_runs());
+        assert_eq!(1, uset![4].count_runs());
+        assert_eq!(1, uset![1, 2, 3].count_runs());
+        assert_eq!(3, uset![1, 3, 5].count_runs());
+
+        let mut s = uset![1, 2, 3, 7, 8];
+        assert_eq!(s.ranges().count(), s.count_runs());
+        s.remove(2);
+        assert_eq!(3, s.count_runs());
+        s.remove(1);
+        assert_eq!(2, s.count_runs());
+    }
+
+    #[test]
+    fn should_dilate_and_erode() {
+        let s = uset![5];
+        assert_eq!(uset![4, 5, 6], s.dilate(1));
+        assert_eq!(uset![5], s.dilate(1).erode(1));
+        assert_eq!(s, s.dilate(0));
+        assert_eq!(s, s.erode(0));
+        assert!(s.erode(1).is_empty());
+
+        // dilation near 0 lowers the minimum and clamps at 0
+        let s2 = uset![1, 10];
+        let d = s2.dilate(3);
+        assert_eq!(Some(0), d.min());
+        assert_eq!(Some(13), d.max());
+        assert_eq!(uset![0, 1, 2, 3, 4, 7, 8, 9, 10, 11, 12, 13], d);
+
+        // erosion removes values whose neighborhood would go below 0
+        let s3 = uset![0, 1, 2, 3];
+        assert_eq!(uset![1, 2], s3.erode(1));
+        assert_eq!(USet::new(), s3.erode(2));
+
+        assert!(USet::new().dilate(2).is_empty());
+        assert!(USet::new().erode(2).is_empty());
+    }
+
+    #[test]
+    fn should_sort_sets_lexicographically() {
+        let mut sets = vec![
+            uset![2],
+            uset![1, 5],
+            USet::new(),
+            uset![1, 2, 3],
+            uset![1, 2],
+        ];
+        sets.sort_by(|a, b| a.cmp_elements(b));
+        assert_eq!(
+            vec![
+                USet::new(),
+                uset![1, 2],
+                uset![1, 2, 3],
+                uset![1, 5],
+                uset![2]
+            ],
+            sets
+        );
+    }
+
+    fn extend_by_push(set: &mut USet, ids: &[usize]) {
+        for &id in ids {
+            set.push(id);
+        }
+    }
+
+    #[test]
+    fn should_extend_like_push() {
+        let ids: Vec<usize> = (0..100).rev().chain(50..150).collect();
+        let mut s1 = uset![120, 500];
+        s1.extend(ids.clone());
+        let mut s2 = uset![120, 500];
+        extend_by_push(&mut s2, &ids);
+        assert_eq!(s2, s1);
+        assert_eq!(151, s1.len());
+        assert_eq!(Some(0), s1.min());
+        assert_eq!(Some(500), s1.max());
+
+        let mut s3 = USet::new();
+        s3.extend(vec![3, 3, 3, 3, 1, 1, 1, 1, 2]);
+        assert_eq!(uset![1, 2, 3], s3);
+        assert_eq!(3, s3.len());
+    }
+
+    quickcheck! {
+        fn extend_same_as_push(initial: Vec<usize>, ids: Vec<usize>) -> bool {
+            let mut s1 = USet::from_slice(&initial);
+            s1.extend(ids.clone());
+            let mut s2 = USet::from_slice(&initial);
+            extend_by_push(&mut s2, &ids);
+            s1 == s2 && s1.len() == s2.len() && s1.iter().eq(s2.iter())
+        }
+    }
+
+    #[test]
+    fn should_bitor() {
+        let s1 = uset![0, 3, 8, 10];
+        let s2 = uset![1, 4];
+        let s3 = USet::new();
+
+        assert_eq!(&s1 + &s2, &s1 | &s2);
+        assert_eq!(&s1 + &s3, &s1 | &s3);
+        assert_eq!(&s3 + &s3, &s3 | &s3);
+
+        let mut s4 = s1.clone();
+        s4 |= &s2;
+        assert_eq!(uset![0, 1, 3, 4, 8, 10], s4);
+        s4 |= &s3;
+        assert_eq!(uset![0, 1, 3, 4, 8, 10], s4);
+    }
+
+    #[test]
+    fn should_take() {
+        let mut s = uset![3, 5, 8];
+        assert_eq!(Some(3), s.take(3));
+        assert_eq!(None, s.take(3));
+        assert_eq!(None, s.take(4));
+        assert_eq!(None, s.take(100));
+        assert_eq!(Some(5), s.min());
+        assert_eq!(2, s.len());
+    }
+
+    #[test]
+    fn should_match_overlap_len_and_contains_subset_with_operators() {
+        let sets = vec![
+            uset![0, 3, 8, 10],
+            uset![3, 8],
+            uset![1, 2, 3],
+            uset![20, 30],
+            USet::new(),
+        ];
+        for a in &sets {
+            for b in &sets {
+                assert_eq!((a * b).len(), a.overlap_len(b));
+                assert_eq!(b.is_subset_of(a), a.contains_subset(b));
+                assert_eq!(&(a * b) == b, a.contains_subset(b));
+            }
+        }
+    }
+
+    #[test]
+    fn should_mirror() {
+        assert_eq!(uset![6, 7], uset![3, 4].mirror(5));
+        assert_eq!(uset![3, 4], uset![3, 4].mirror(5).mirror(5));
+        assert_eq!(uset![5], uset![5].mirror(5));
+        assert_eq!(uset![0, 10], uset![0, 10].mirror(5));
+        // reflections below 0 are rejected
+        assert_eq!(uset![0], uset![4, 5].mirror(2));
+        assert_eq!(USet::new(), uset![11].mirror(5));
+        assert_eq!(uset![usize::MAX], uset![0, 1].mirror(usize::MAX / 2 + 1));
+        assert!(USet::new().mirror(3).is_empty());
+    }
+
+    #[test]
+    fn should_drain_range() {
+        let mut s = uset![1, 3, 5, 8, 10];
+        let drained = s.drain_range(2..9);
+        assert_eq!(uset![3, 5, 8], drained);
+        assert_eq!(3, drained.len());
+        assert_eq!(uset![1, 10], s);
+        assert_eq!(2, s.len());
+
+        // draining the lower end moves the minimum
+        let drained = s.drain_range(0..5);
+        assert_eq!(uset![1], drained);
+        assert_eq!(Some(10), s.min());
+        assert_eq!(Some(10), s.max());
+
+        assert!(s.drain_range(11..20).is_empty());
+        assert!(s.drain_range(5..5).is_empty());
+
+        let drained = s.drain_range(0..usize::MAX);
+        assert_eq!(uset![10], drained);
+        assert!(s.is_empty());
+        assert!(USet::new().drain_range(0..10).is_empty());
+
+        // draining the upper end moves the maximum
+        let mut s2 = uset![2, 4, 6];
+        assert_eq!(uset![4, 6], s2.drain_range(3..100));
+        assert_eq!(Some(2), s2.max());
+    }
+
+    #[test]
+    fn should_format_as_binary() {
+        assert_eq!("1101", format!("{:b}", uset![0, 2, 3]));
+        assert_eq!("1101", format!("{:b}", uset![10, 12, 13]));
+        assert_eq!("1", format!("{:b}", uset![7]));
+        assert_eq!("0", format!("{:b}", USet::new()));
+        assert_eq!("00001101", format!("{:08b}", uset![0, 2, 3]));
+    }
+
+    #[test]
+    fn should_iterate_over_range() {
+        let s = uset![1, 3, 8, 10];
+        assert_eq!(vec![3, 8], s.range(2..9).collect::<Vec<_>>());
+        assert_eq!(vec![8, 3], s.range(2..9).rev().collect::<Vec<_>>());
+        assert_eq!(vec![1, 3, 8, 10], s.range(0..100).collect::<Vec<_>>());
+        assert_eq!(vec![10], s.range(10..11).collect::<Vec<_>>());
+        assert_eq!(0, s.range(4..8).count());
+        assert_eq!(0, s.range(11..20).count());
+        assert_eq!(0, s.range(0..1).count());
+        assert_eq!(0, s.range(9..9).count());
+        assert_eq!(0, USet::new().range(0..10).count());
+
+        let top = uset![usize::MAX - 1, usize::MAX];
+        assert_eq!(
+            vec![usize::MAX - 1],
+            top.range(0..usize::MAX).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_count_ones_and_zeros() {
+        let s = uset![1, 3, 8, 10];
+        assert_eq!(2, s.count_ones(0..5));
+        assert_eq!(3, s.count_zeros(0..5));
+        assert_eq!(0, s.count_ones(4..8));
+        assert_eq!(4, s.count_zeros(4..8));
+        assert_eq!(0, s.count_ones(5..5));
+        assert_eq!(0, s.count_zeros(5..5));
+        assert_eq!(0, USet::new().count_ones(0..5));
+        assert_eq!(5, USet::new().count_zeros(0..5));
+    }
+
+    #[test]
+    fn should_grow_to_include_without_inserting() {
+        let mut set = USet::from_slice(&[10, 12]);
+        set.grow_to_include(3);
+        set.grow_to_include(30);
+        let capacity = set.capacity();
+        assert_eq!(2, set.len());
+        assert_eq!(USet::from_slice(&[10, 12]), set);
+        set.push(3);
+        set.push(30);
+        assert_eq!(capacity, set.capacity());
+        assert_eq!(USet::from_slice(&[3, 10, 12, 30]), set);
+
+        let mut empty = USet::new();
+        empty.grow_to_include(100);
+        let capacity = empty.capacity();
+        assert!(empty.is_empty());
+        assert_eq!(USet::new(), empty);
+        empty.push(100);
+        assert_eq!(capacity, empty.capacity());
+
+        let mut empty = USet::new();
+        empty.grow_to_include(10);
+        assert_eq!(USet::new(), empty);
+    }
+
+    #[test]
+    fn should_compute_jaccard_distance() {
+        let set = USet::from_slice(&[1, 4, 7, 9]);
+        assert_eq!(0.0, set.jaccard_distance(&set));
+        assert_eq!(1.0, set.jaccard_distance(&USet::from_slice(&[2, 3, 10])));
+        assert_eq!(1.0, set.jaccard_distance(&USet::new()));
+        assert_eq!(0.0, USet::new().jaccard_distance(&USet::new()));
+    }
+
+    quickcheck! {
+        fn difference_lens_same_as_materialized(v1: Vec<usize>, v2: Vec<usize>) -> bool {
+            let s1 = USet::from_slice(&v1);
+            let s2 = USet::from_slice(&v2);
+            s1.difference_len(&s2) == (&s1 - &s2).len()
+                && s2.difference_len(&s1) == (&s2 - &s1).len()
+                && s1.symmetric_difference_len(&s2) == (&s1 ^ &s2).len()
+        }
+    }
+
+    quickcheck! {
+        fn capacity_for_same_as_from_slice(v: Vec<usize>) -> TestResult {
+            let set = USet::from_slice(&v);
+            if set.len() < 2 {
+                return TestResult::discard()
+            }
+            let (min, max) = (set.min().unwrap(), set.max().unwrap());
+            TestResult::from_bool(USet::capacity_for(min, max) == set.capacity())
+        }
+    }
+
+    #[test]
+    fn should_not_waste_capacity_after_shrink_to_fit() {
+        let mut set = USet::with_capacity(100);
+        for id in &[40, 45, 60] {
+            set.push(*id);
+        }
+        assert_eq!(79, set.wasted_capacity());
+        set.shrink_to_fit();
+        assert_eq!(0, set.wasted_capacity());
+        assert_eq!(21, set.capacity());
+        assert_eq!(0, USet::new().wasted_capacity());
+    }
+
+    #[test]
+    fn should_compact() {
+        let mut set = USet::with_capacity(100);
+        for id in &[40, 45, 60] {
+            set.push(*id);
+        }
+        set.compact();
+        assert_eq!(21, set.capacity());
+        assert_eq!(0, set.wasted_capacity());
+        assert_eq!(set, USet::from_slice(&[40, 45, 60]));
+        assert_eq!(vec![40, 45, 60], set.iter().collect::<Vec<usize>>());
+
+        // with offset == min, ids up to min + capacity - 1 do not need reallocation
+        set.remove(60);
+        set.push(60);
+        assert_eq!(21, set.capacity());
+
+        let mut empty = USet::with_capacity(10);
+        empty.compact();
+        assert_eq!(0, empty.capacity());
+    }
+
+    quickcheck! {
+        fn iteration_independent_of_construction(ids: Vec<usize>, extras: Vec<usize>) -> bool {
+            let s1 = USet::from_slice(&ids);
+            let mut s2 = USet::new();
+            extras.iter().chain(ids.iter()).for_each(|&id| s2.push(id));
+            extras
+                .iter()
+                .filter(|id| !ids.contains(id))
+                .for_each(|&id| s2.remove(id));
+            s1 == s2
+                && s1.iter().collect::<Vec<usize>>() == s2.iter().collect::<Vec<usize>>()
+                && s1.iter().rev().collect::<Vec<usize>>() == s2.iter().rev().collect::<Vec<usize>>()
+        }
+    }
+
+    #[test]
+    fn should_seek_and_peek_with_cursor() {
+        let set = uset![1, 3, 8, 10];
+        let mut cursor = set.cursor();
+        assert_eq!(Some(1), cursor.peek());
+        assert_eq!(Some(1), cursor.peek());
+        cursor.seek(2);
+        assert_eq!(Some(3), cursor.peek());
+        assert_eq!(Some(3), cursor.next());
+        cursor.seek(8);
+        assert_eq!(Some(8), cursor.peek());
+        cursor.seek(9);
+        assert_eq!(Some(10), cursor.next());
+        assert_eq!(None, cursor.peek());
+        assert_eq!(None, cursor.next());
+        cursor.seek(0);
+        assert_eq!(vec![1, 3, 8, 10], cursor.collect::<Vec<usize>>());
+
+        let mut cursor = set.cursor();
+        cursor.seek(100);
+        assert_eq!(None, cursor.next());
+        assert_eq!(None, USet::new().cursor().peek());
+    }
+
+    #[test]
+    fn should_remove_all_from_slice() {
+        let mut set = uset![1, 3, 5, 8, 10, 12];
+        let ids = [3, 4, 10, 12, 100];
+        set.remove_all(ids.iter().copied());
+        assert_eq!(uset![1, 5, 8], set);
+        assert_eq!(3, set.len());
+
+        set.remove_all([1, 8]);
+        assert_eq!(uset![5], set);
+        set.remove_all(0..10);
+        assert!(set.is_empty());
+    }
+
+    quickcheck! {
+        fn from_iter_same_as_from_slice(ids: Vec<usize>) -> bool {
+            let s1: USet = ids.iter().copied().collect();
+            let s2 = USet::from_slice(&ids);
+            s1 == s2 && s1.len() == s2.len() && s1.iter().eq(s2.iter())
+        }
+    }
+
+    #[test]
+    fn should_collect_descending_ids() {
+        let set: USet = (0..1000).rev().chain(500..1500).collect();
+        assert_eq!(1500, set.len());
+        assert_eq!(Some(0), set.min());
+        assert_eq!(Some(1499), set.max());
+        assert_eq!(set, USet::from_slice(&(0..1500).collect::<Vec<usize>>()));
+    }
+
+    #[test]
+    fn should_pop_back() {
+        let mut set = uset![2, 5, 9];
+        assert_eq!(Some(9), set.pop_back());
+        assert_eq!(Some(5), set.pop_back());
+        assert_eq!(Some(2), set.pop_back());
+        assert_eq!(None, set.pop_back());
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn should_pop_random() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let original = uset![1, 4, 6, 10, 11];
+        let mut set = original.clone();
+        let mut popped = Vec::new();
+        while let Some(id) = set.pop_random(&mut rng) {
+            assert!(original.contains(id));
+            assert!(!set.contains(id));
+            popped.push(id);
+        }
+        assert!(set.is_empty());
+        assert_eq!(original, USet::from_slice(&popped));
+        assert_eq!(5, popped.len());
+    }
+
+    #[test]
+    fn should_shift_in_each_arith_mode() {
+        use crate::core::id_arith::*;
+        let low = uset![0, 1, 5];
+        assert_eq!(Err(IdOverflow { id: 0 }), low.shift(-1));
+        assert_eq!(Ok(uset![0, 4]), low.shift_with(-1, IdArith::Saturating));
+
+        let high = uset![usize::MAX - 1, usize::MAX];
+        assert_eq!(Err(IdOverflow { id: usize::MAX }), high.shift(1));
+        assert_eq!(
+            Ok(uset![usize::MAX]),
+            high.shift_with(1, IdArith::Saturating)
+        );
+        assert_eq!(Ok(uset![0, 1]), high.shift_with(2, IdArith::Wrapping));
+        assert_eq!(
+            Ok(high.clone()),
+            uset![0, 1].shift_with(-2, IdArith::Wrapping)
+        );
+        assert_eq!(Ok(USet::new()), USet::new().shift(-1));
+
+        // only some values wrap around
+        assert_eq!(
+            Err(IdOverflow { id: 0 }),
+            uset![0, 5].shift_with(-1, IdArith::Wrapping)
+        );
+        assert_eq!(
+            Err(IdOverflow { id: usize::MAX }),
+            uset![usize::MAX - 3, usize::MAX].shift_with(1, IdArith::Wrapping)
+        );
+    }
+
+    #[test]
+    fn should_mirror_and_dilate_in_each_arith_mode() {
+        use crate::core::id_arith::*;
+        let set = uset![1, 2];
+        let center = usize::MAX - 1;
+        assert_eq!(
+            Err(IdOverflow { id: 1 }),
+            set.mirror_with(center, IdArith::Checked)
+        );
+        assert_eq!(
+            Ok(uset![usize::MAX]),
+            set.mirror_with(center, IdArith::Saturating)
+        );
+        assert_eq!(
+            Ok(uset![usize::MAX - 5, usize::MAX - 4]),
+            set.mirror_with(center, IdArith::Wrapping)
+        );
+
+        assert_eq!(
+            Err(IdOverflow { id: 1 }),
+            set.dilate_with(2, IdArith::Checked)
+        );
+        assert_eq!(
+            Ok(uset![0, 1, 2, 3, 4]),
+            set.dilate_with(2, IdArith::Saturating)
+        );
+        let high = uset![usize::MAX - 1];
+        assert_eq!(
+            Err(IdOverflow { id: usize::MAX - 1 }),
+            high.dilate_with(2, IdArith::Checked)
+        );
+        assert_eq!(
+            Ok(uset![usize::MAX - 2, usize::MAX - 1, usize::MAX]),
+            high.dilate_with(1, IdArith::Wrapping)
+        );
+        let two = uset![3, 10];
+        assert_eq!(Ok(two.dilate(2)), two.dilate_with(2, IdArith::Wrapping));
+
+        // only some values wrap around
+        assert_eq!(
+            Err(IdOverflow { id: 5 }),
+            uset![1, 5].mirror_with(2, IdArith::Wrapping)
+        );
+        assert_eq!(
+            Err(IdOverflow { id: 1 }),
+            uset![1, 8].dilate_with(2, IdArith::Wrapping)
+        );
+        assert_eq!(
+            Err(IdOverflow { id: usize::MAX - 1 }),
+            high.dilate_with(2, IdArith::Wrapping)
+        );
+    }
+
+    quickcheck! {
+        fn invariants_hold_after_random_operations(ops: Vec<(u8, usize)>) -> bool {
+            let mut set = USet::new();
+            for (op, arg) in ops {
+                let id = arg % 200;
+                match op % 8 {
+                    0 | 1 => set.push(id),
+                    2 => set.remove(id),
+                    3 => {
+                        let drained = set.drain(id % 10);
+                        drained.check_invariants();
+                    }
+                    4 => {
+                        let drained = set.drain_range(id..id + 20);
+                        drained.check_invariants();
+                    }
+                    5 => set.push_all(&[id, id / 2, id + 7]),
+                    6 => set.truncate(id % 10),
+                    _ => {
+                        set.pop_back();
+                    }
+                }
+                set.check_invariants();
+            }
+            true
+        }
+    }
+
+    #[test]
+    fn should_create_from_range_with_correct_max() {
+        let set = USet::from(3..6);
+        set.check_invariants();
+        assert_eq!(Some(5), set.max());
+        assert_eq!(set, uset![3, 4, 5]);
+    }
+
+    #[test]
+    fn should_drain_iter_partially() {
+        let mut set = uset![2, 4, 5, 9, 12, 20];
+        {
+            let mut drain = set.drain_iter();
+            assert_eq!(Some(2), drain.next());
+            assert_eq!(Some(4), drain.next());
+            assert_eq!(Some(5), drain.next());
+        }
+        assert_eq!(uset![9, 12, 20], set);
+        assert_eq!(3, set.len());
+        assert_eq!(Some(9), set.min());
+
+        let rest: Vec<usize> = set.drain_iter().collect();
+        assert_eq!(vec![9, 12, 20], rest);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn should_intersect_with_raw_mask() {
+        // bits 0, 3, 63 in the first word and 0, 5 in the second, starting at 100
+        let mask = [1u64 | 1 << 3 | 1 << 63, 1 | 1 << 5];
+        let mut set = uset![50, 100, 101, 103, 150, 163, 164, 165, 169, 300];
+        set.mask_and(100, &mask);
+        assert_eq!(uset![100, 103, 163, 164, 169], set);
+        assert_eq!(5, set.len());
+
+        set.mask_and(0, &[]);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn should_allocate_according_to_growth_policy() {
+        let mut set = USet::new();
+        set.push(10);
+        assert_eq!(INITIAL_WORKING_CAPACITY, set.capacity());
+        set.push(20);
+        assert_eq!(11, set.capacity());
+
+        // the default policy sizes the first bulk allocation like `from_slice`
+        let mut set = USet::new();
+        set.push_all(&[5]);
+        assert_eq!(1, set.capacity());
+        let mut set = USet::new();
+        set.push_all(&[5, 7]);
+        assert_eq!(INITIAL_WORKING_CAPACITY, set.capacity());
+        let mut set = USet::new();
+        set.push_all(&[5, 24]);
+        assert_eq!(20, set.capacity());
+
+        let mut set = USet::new().with_growth_policy(GrowthPolicy::Fixed(32));
+        set.push(10);
+        assert_eq!(32, set.capacity());
+        set.push(50);
+        assert_eq!(41, set.capacity());
+
+        let mut set = USet::new().with_growth_policy(GrowthPolicy::Exact);
+        set.push(10);
+        assert_eq!(1, set.capacity());
+        set.push(12);
+        assert_eq!(3, set.capacity());
+        set.push(8);
+        assert_eq!(5, set.capacity());
+
+        let mut set = USet::new().with_growth_policy(GrowthPolicy::Double);
+        set.push(10);
+        assert_eq!(INITIAL_WORKING_CAPACITY, set.capacity());
+        set.push(20);
+        assert_eq!(16, set.capacity());
+        set.push(5);
+        assert_eq!(32, set.capacity());
+        set.push_all(&[1, 100]);
+        assert_eq!(100, set.capacity());
+        assert_eq!(uset![1, 5, 10, 20, 100], set);
+        assert_eq!(GrowthPolicy::Double, set.growth_policy());
+    }
+
+    #[cfg(feature = "realloc-hook")]
+    #[test]
+    fn should_report_reallocations_to_hook() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let mut set = USet::new();
+        set.on_realloc(move |old, new| recorded.lock().unwrap().push((old, new)));
+
+        set.push(10);
+        set.push(12);
+        set.push(20);
+        set.push(5);
+        set.push_all(&[1, 30]);
+        assert_eq!(
+            vec![(0, INITIAL_WORKING_CAPACITY), (8, 11), (11, 16), (16, 30)],
+            *events.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn should_join_with_subset_without_reallocating() {
+        let mut big = USet::with_capacity(100);
+        (1..=5).for_each(|id| big.push(id));
+        let small = uset![2, 4];
+
+        let joined = &big + &small;
+        assert_eq!(uset![1, 2, 3, 4, 5], joined);
+        assert_eq!(100, joined.capacity());
+
+        let joined = &small + &big;
+        assert_eq!(uset![1, 2, 3, 4, 5], joined);
+        assert_eq!(100, joined.capacity());
+
+        let other = uset![2, 6];
+        let joined = &big + &other;
+        assert_eq!(uset![1, 2, 3, 4, 5, 6], joined);
+        assert_eq!(6, joined.capacity());
+    }
+
+    #[test]
+    fn should_summarize_set() {
+        let set = uset![3, 4, 5, 10, 20, 21];
+        assert_eq!(
+            "USet{len=6, span=3..=21, runs=3, density=0.32}",
+            set.summary()
+        );
+    }
+
+    #[test]
+    fn should_filter_present_ids() {
+        let ids = [2, 4, 5, 9];
+        assert!(uset![1, 3, 8, 10].filter_present(&ids).is_empty());
+        assert_eq!(uset![2, 5], uset![2, 5].filter_present(&ids));
+        assert_eq!(uset![2, 5], uset![0, 2, 5, 7].filter_present(&[5, 2, 5, 2]));
+        assert!(USet::new().filter_present(&ids).is_empty());
+    }
+
+    #[test]
+    fn should_index_into_slice() {
+        let letters = ["a", "b", "c"];
+        let set = uset![0, 2];
+        let pairs: Vec<(usize, &&str)> = set.index_into(&letters).collect();
+        assert_eq!(vec![(0, &"a"), (2, &"c")], pairs);
+
+        assert_eq!(0, uset![3, 4].index_into(&letters).count());
+        assert_eq!(0, USet::new().index_into(&letters).count());
+        assert_eq!(0, uset![0, 1].index_into::<&str>(&[]).count());
+    }
+
+    #[test]
+    fn should_build_from_range_where() {
+        let evens = USet::from_range_where(0..10, |id| id % 2 == 0);
+        assert_eq!(uset![0, 2, 4, 6, 8], evens);
+        assert_eq!(5, evens.len());
+        assert_eq!(Some(0), evens.min());
+        assert_eq!(Some(8), evens.max());
+        assert_eq!(10, evens.capacity());
+
+        let odds = USet::from_range_where(100..110, |id| id % 2 == 1);
+        assert_eq!(uset![101, 103, 105, 107, 109], odds);
+        assert!(USet::from_range_where(5..5, |_| true).is_empty());
+    }
+
+    #[test]
+    fn should_subtract_in_place() {
+        let mut set = USet::with_capacity(50);
+        [10, 12, 14, 16].iter().for_each(|&id| set.push(id));
+        set.difference_into(&uset![10, 16, 30]);
+        assert_eq!(uset![12, 14], set);
+        assert_eq!(Some(12), set.min());
+        assert_eq!(Some(14), set.max());
+        assert_eq!(50, set.capacity());
+
+        set.difference_into(&uset![1, 12, 13, 14, 15]);
+        assert!(set.is_empty());
+        assert_eq!(0, set.len());
+        assert_eq!(None, set.min());
+        assert_eq!(None, set.max());
+        assert_eq!(50, set.capacity());
+
+        set.push(3);
+        assert_eq!(uset![3], set);
+        assert_eq!(Some(3), set.min());
+    }
+
+    #[test]
+    fn should_subtract_in_place_at_the_top_of_the_range() {
+        let mut set = uset![usize::MAX - 2, usize::MAX - 1, usize::MAX];
+        set.difference_into(&uset![usize::MAX - 2]);
+        assert_eq!(uset![usize::MAX - 1, usize::MAX], set);
+        set.difference_into(&uset![usize::MAX]);
+        assert_eq!(uset![usize::MAX - 1], set);
+        assert_eq!(Some(usize::MAX - 1), set.max());
+    }
+
+    #[test]
+    fn should_return_min_and_max_unchecked() {
+        let mut set = uset![4, 9, 13];
+        assert_eq!(4, set.min_unchecked());
+        assert_eq!(13, set.max_unchecked());
+        set.push(1);
+        assert_eq!(1, set.min_unchecked());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "min_unchecked called on an empty set")]
+    fn should_panic_on_min_unchecked_of_empty_set() {
+        USet::new().min_unchecked();
+    }
+
+    #[test]
+    fn rank_select_index_same_as_at_index() {
+        let set = uset![3, 17, 18, 250, 1000, 1001, 4096];
+        let index = set.build_index();
+        assert_eq!(set.len(), index.len());
+        for k in 0..=set.len() {
+            assert_eq!(set.at_index(k), index.select(k));
+        }
+        for (k, id) in set.iter().enumerate() {
+            assert_eq!(k, index.rank(id));
+        }
+        assert_eq!(0, index.rank(0));
+        assert_eq!(3, index.rank(100));
+        assert_eq!(7, index.rank(5000));
+        assert!(USet::new().build_index().is_empty());
+    }
+
+    #[test]
+    fn should_iterate_over_edges() {
+        let edges: Vec<(usize, bool)> = uset![1, 2, 4].edges().collect();
+        assert_eq!(vec![(1, true), (3, false), (4, true), (5, false)], edges);
+
+        let edges: Vec<(usize, bool)> = uset![0, 1, 2, 3].edges().collect();
+        assert_eq!(vec![(0, true), (4, false)], edges);
+
+        assert_eq!(0, USet::new().edges().count());
+
+        let top = uset![usize::MAX - 3, usize::MAX - 1, usize::MAX];
+        assert_eq!(
+            vec![
+                (usize::MAX - 3, true),
+                (usize::MAX - 2, false),
+                (usize::MAX - 1, true)
+            ],
+            top.edges().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_reset_to_new_window_reusing_buffer() {
+        let mut set = USet::with_capacity(64);
+        (0..20).for_each(|id| set.push(id));
+        set.reset_to(1000, 1030);
+        assert!(set.is_empty());
+        assert_eq!(64, set.capacity());
+        set.push(1000);
+        set.push(1030);
+        assert_eq!(uset![1000, 1030], set);
+        assert_eq!(64, set.capacity());
+
+        set.reset_to(0, 99);
+        assert!(set.is_empty());
+        assert_eq!(100, set.capacity());
+
+        let mut set = USet::from_slice(&[1, 2]);
+        set.reset_to(50, 60);
+        assert_eq!(USet::new(), set);
+    }
+
+    #[test]
+    fn should_xor_with_range() {
+        let set = uset![1, 3, 8];
+        assert_eq!(uset![1, 2, 4, 8], set.symmetric_difference_range(2..5));
+        assert_eq!(
+            uset![1, 3, 8, 20, 21],
+            set.symmetric_difference_range(20..22)
+        );
+        assert!(uset![2, 3, 4].symmetric_difference_range(2..5).is_empty());
+        assert_eq!(uset![5, 6], USet::new().symmetric_difference_range(5..7));
+    }
+
+    quickcheck! {
+        fn xor_range_same_as_xor_set(ids: Vec<u8>, start: u8, len: u8) -> bool {
+            let ids: Vec<usize> = ids.into_iter().map(|id| id as usize).collect();
+            let set = USet::from_slice(&ids);
+            let range = start as usize..start as usize + len as usize % 32;
+            let result = set.symmetric_difference_range(range.clone());
+            result.check_invariants();
+            result == &set ^ &USet::from_range(range)
+        }
+    }
+
+    #[test]
+    fn should_verify_len() {
+        let mut set = uset![1, 2, 2, 5];
+        assert!(set.verify_len());
+        set.push_all(&[5, 7, 7]);
+        set.remove(1);
+        assert!(set.verify_len());
+        assert!(USet::new().verify_len());
+        assert!(USet::from_range_where(0..100, |id| id % 3 == 0).verify_len());
+
+        set.corrupt_len(set.len() + 1);
+        assert!(!set.verify_len());
+    }
+
+    #[test]
+    fn should_write_to_and_read_from_stream() {
+        use std::io::Cursor;
+
+        let sets = vec![
+            USet::new(),
+            uset![0],
+            uset![1, 2, 3, 200, 201, 5000],
+            USet::from_range(1_000_000..1_000_100),
+            uset![usize::MAX - 3, usize::MAX - 2],
+            uset![usize::MAX - 3, usize::MAX - 1, usize::MAX],
+            uset![usize::MAX],
+        ];
+        for set in sets {
+            let mut cursor = Cursor::new(Vec::new());
+            set.write_to(&mut cursor).unwrap();
+            cursor.set_position(0);
+            assert_eq!(set, USet::read_from(&mut cursor).unwrap());
+        }
+
+        let mut truncated = Cursor::new(vec![2, 1, 3]);
+        assert!(USet::read_from(&mut truncated).is_err());
+        let mut overflowing = Cursor::new(vec![
+            1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 1,
+        ]);
+        assert_eq!(
+            std::io::ErrorKind::InvalidData,
+            USet::read_from(&mut overflowing).unwrap_err().kind()
+        );
+
+        // a second run after the one ending at usize::MAX
+        let mut past_max = Vec::new();
+        uset![usize::MAX].write_to(&mut past_max).unwrap();
+        past_max[0] = 2;
+        past_max.extend(&[0, 1]);
+        assert_eq!(
+            std::io::ErrorKind::InvalidData,
+            USet::read_from(&mut past_max.as_slice())
+                .unwrap_err()
+                .kind()
+        );
+    }
+
+    #[test]
+    fn should_reject_huge_spans_when_reading_from_stream() {
+        use std::io::{Cursor, ErrorKind};
+
+        // one run of 2^40 values starting at 0
+        let mut huge_run = Cursor::new(vec![1, 0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x20]);
+        assert_eq!(
+            ErrorKind::InvalidData,
+            USet::read_from(&mut huge_run).unwrap_err().kind()
+        );
+
+        // two one-value runs with a gap of 2^40 between them
+        let mut huge_gap = Cursor::new(vec![2, 0, 1, 0x80, 0x80, 0x80, 0x80, 0x80, 0x20, 1]);
+        assert_eq!(
+            ErrorKind::InvalidData,
+            USet::read_from(&mut huge_gap).unwrap_err().kind()
+        );
+
+        let set = uset![5, 6, 7, 20];
+        let mut bytes = Vec::new();
+        set.write_to(&mut bytes).unwrap();
+        assert_eq!(
+            set,
+            USet::read_from_with_limit(&mut bytes.as_slice(), 16).unwrap()
+        );
+        assert_eq!(
+            ErrorKind::InvalidData,
+            USet::read_from_with_limit(&mut bytes.as_slice(), 15)
+                .unwrap_err()
+                .kind()
+        );
+    }
+
+    quickcheck! {
+        fn write_read_round_trip(ids: Vec<u16>) -> bool {
+            let ids: Vec<usize> = ids.into_iter().map(|id| id as usize).collect();
+            let set = USet::from_slice(&ids);
+            let mut bytes = Vec::new();
+            set.write_to(&mut bytes).unwrap();
+            USet::read_from(&mut bytes.as_slice()).unwrap() == set
+        }
+    }
+
+    #[test]
+    fn should_build_from_unsorted() {
+        let set = USet::from_unsorted(vec![3, 1, 1, 2]);
+        assert_eq!(uset![1, 2, 3], set);
+        assert_eq!(3, set.len());
+        assert!(set.verify_len());
+        assert!(USet::from_unsorted(Vec::new()).is_empty());
+    }
+
+    quickcheck! {
+        fn from_unsorted_same_as_from_slice(ids: Vec<u16>) -> bool {
+            let ids: Vec<usize> = ids.into_iter().map(|id| id as usize).collect();
+            let set = USet::from_unsorted(ids.clone());
+            set.check_invariants();
+            set == USet::from_slice(&ids) && set.len() == USet::from_slice(&ids).len()
+        }
+    }
+
+    quickcheck! {
+        fn offset_never_above_min(ops: Vec<(u8, usize)>) -> bool {
+            let mut set = USet::new();
+            let mut map = UMap::new();
+            ops.into_iter().all(|(op, arg)| {
+                let id = arg % 200;
+                match op % 6 {
+                    0 | 1 => {
+                        set.push(id);
+                        map.put(id, op);
+                    }
+                    2 => {
+                        set.remove(id);
+                        map.remove(id);
+                    }
+                    3 => {
+                        set.push_all(&[id, id / 2]);
+                        map.put_all(&[(id, op), (id / 2, op)]);
+                    }
+                    4 => {
+                        set.shrink_to_fit();
+                        map.shrink_to_fit();
+                    }
+                    _ => {
+                        set.truncate(id % 5);
+                        map.truncate(id % 5);
+                    }
+                }
+                set.min().map_or(true, |min| set.offset() <= min)
+                    && map.min().map_or(true, |min| map.offset() <= min)
+            })
+        }
+    }
+
+    quickcheck! {
+        fn is_subset_of_same_as_contains(a: Vec<u8>, b: Vec<u8>) -> bool {
+            let a: Vec<usize> = a.into_iter().map(|id| id as usize % 64).collect();
+            let b: Vec<usize> = b.into_iter().map(|id| id as usize % 64).collect();
+            let set_a = USet::from_slice(&a);
+            let set_b = USet::from_slice(&b);
+            let expected = set_a.iter().all(|id| set_b.contains(id));
+            set_a.is_subset_of(&set_b) == expected && set_a.is_subset_of(&(&set_a + &set_b))
+        }
+    }
+
+    #[test]
+    fn should_reject_too_large_span_in_try_from_slice() {
+        use crate::core::error::UsetError;
+
+        assert_eq!(
+            Err(UsetError::TooLarge),
+            USet::try_from_slice(&[0, 10_000_000_000])
+        );
+        assert_eq!(Ok(uset![0, 10]), USet::try_from_slice(&[0, 10]));
+        assert_eq!(Ok(USet::new()), USet::try_from_slice(&[]));
+
+        assert!(USet::try_from_slice_with_limit(&[5, 14], 10).is_ok());
+        assert!(USet::try_from_slice_with_limit(&[5, 15], 10).is_err());
+    }
+
+    #[test]
+    fn should_collect_to_vec_like_iter() {
+        let set = uset![3, 5, 8, 100];
+        assert_eq!(set.iter().collect::<Vec<usize>>(), set.to_vec());
+        assert_eq!(vec![3, 5, 8, 100], set.to_vec());
+        assert!(USet::new().to_vec().is_empty());
+    }
+
+    #[test]
+    fn should_report_reallocation_in_push_all() {
+        let mut set = USet::with_capacity(16);
+        set.push(4);
+        let capacity = set.capacity();
+        assert!(!set.push_all_reporting(&[4, 6, 15]));
+        assert_eq!(capacity, set.capacity());
+        assert_eq!(uset![4, 6, 15], set);
+        assert!(!set.push_all_reporting(&[]));
+        assert!(set.push_all_reporting(&[4 + capacity]));
+        assert_eq!(uset![4, 6, 15, 4 + capacity], set);
+        assert!(set.push_all_reporting(&[1]));
+        assert_eq!(Some(1), set.min());
+    }
+
+    #[test]
+    fn should_not_report_reallocation_in_push_all_on_empty_set_with_capacity() {
+        let mut set = USet::with_capacity(10);
+        assert!(!set.push_all_reporting(&[1, 2]));
+        assert_eq!(10, set.capacity());
+        assert_eq!(uset![1, 2], set);
+
+        let mut set = USet::with_range_capacity(0, 100);
+        assert!(!set.push_all_reporting(&[1, 2, 100]));
+        assert_eq!(101, set.capacity());
+
+        let mut set = USet::with_capacity(10);
+        assert!(!set.push_all_reporting(&[500, 509]));
+        assert_eq!(10, set.capacity());
+        assert_eq!(uset![500, 509], set);
+        assert!(set.push_all_reporting(&[510]));
+
+        assert!(USet::new().push_all_reporting(&[1]));
+    }
+
+    #[test]
+    fn should_return_span() {
+        assert_eq!(None, USet::new().span());
+        assert_eq!(Some(7..=7), uset![7].span());
+        let mut set = uset![2, 5, 11];
+        assert_eq!(Some(2..=11), set.span());
+        set.remove(11);
+        assert_eq!(Some(2..=5), set.span());
+    }
+
+    #[test]
+    fn should_construct_new_like_empty_set() {
+        let set = USet::new();
+        assert_eq!(EMPTY_SET.clone(), set);
+        assert_eq!(0, set.capacity());
+        assert_eq!(0, set.len());
+        assert_eq!(0, set.offset());
+        assert_eq!(None, set.min());
+        assert_eq!(None, set.max());
+        assert_eq!(EMPTY_SET.growth_policy(), set.growth_policy());
+        assert_eq!(USet::default(), set);
+    }
+
+    #[test]
+    fn should_return_membership_mask() {
+        let set = uset![1, 3];
+        assert_eq!(
+            vec![true, false, true, false],
+            set.membership_mask(&[1, 2, 3, 4])
+        );
+        assert!(set.membership_mask(&[]).is_empty());
+        assert_eq!(vec![false, false], USet::new().membership_mask(&[0, 1]));
+
+        let mut set = USet::with_capacity(16);
+        set.push_all(&[5, 6, 7]);
+        set.remove(7);
+        let ids = [0, 4, 5, 6, 7, 15, 16, usize::MAX];
+        let expected: Vec<bool> = ids.iter().map(|&id| set.contains(id)).collect();
+        assert_eq!(expected, set.membership_mask(&ids));
+    }
+
+    #[test]
+    fn should_keep_growth_policy_in_place() {
+        let mut set = USet::new().with_growth_policy(GrowthPolicy::Exact);
+        set.push(1);
+        set |= &uset![5, 6];
+        assert_eq!(GrowthPolicy::Exact, set.growth_policy());
+        set.push(10);
+        assert_eq!(10, set.capacity());
+
+        let mut set = USet::new().with_growth_policy(GrowthPolicy::Exact);
+        set.grow_to_include(3);
+        assert_eq!(1, set.capacity());
+        set.push(3);
+        set.grow_to_include(7);
+        assert_eq!(5, set.capacity());
+        set.grow_to_include(1);
+        assert_eq!(7, set.capacity());
+        assert_eq!(1, set.offset());
+
+        let mut set = USet::new().with_growth_policy(GrowthPolicy::Exact);
+        set.push_all(&[4, 5]);
+        assert_eq!(2, set.capacity());
+
+        let mut set = USet::new().with_growth_policy(GrowthPolicy::Double);
+        set.push(0);
+        set.grow_to_include(8);
+        assert_eq!(16, set.capacity());
+    }
+
+    #[cfg(feature = "realloc-hook")]
+    #[test]
+    fn should_keep_realloc_hook_in_place() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        let mut set = uset![0, 3];
+        set.on_realloc(move |old, new| recorded.lock().unwrap().push((old, new)));
+
+        let capacity = set.capacity();
+        set.grow_to_include(capacity);
+        assert_eq!(vec![(capacity, set.capacity())], *events.lock().unwrap());
+
+        let old_capacity = set.capacity();
+        set |= &uset![20, 30];
+        let capacity = set.capacity();
+        assert_eq!(
+            (old_capacity, capacity),
+            *events.lock().unwrap().last().unwrap()
+        );
+        assert_eq!(uset![0, 3, 20, 30], set);
+
+        set.remove(30);
+        set.shrink_to(0);
+        assert_eq!((capacity, 21), *events.lock().unwrap().last().unwrap());
+        assert_eq!(3, events.lock().unwrap().len());
+    }
 }
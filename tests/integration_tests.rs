@@ -1,6 +1,4 @@
 extern crate uset;
 
-use uset::core::uset::*;
-
 #[test]
 fn foo() {}